@@ -1 +1,46 @@
-test
\ No newline at end of file
+(module
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 12) "\0a")
+  (data (i32.const 28) "Bonjour de Gaufre!")
+  (data (i32.const 46) "hello from utils!")
+  (data (i32.const 63) "hello from unit!")
+  (func $print (param $ptr i32) (param $len i32)
+    (i32.store (i32.const 0) (local.get $ptr))
+    (i32.store (i32.const 4) (local.get $len))
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+  )
+  (func $u_main
+    ;; exemple/hello.gfr:5:3
+    i32.const 28
+    i32.const 18
+    call $print
+    i32.const 12
+    i32.const 1
+    call $print
+    ;; exemple/hello.gfr:6:3
+    call $u_hello_from_unit
+    ;; exemple/hello.gfr:7:3
+    call $u_hello_from_utils
+  )
+  (func $u_hello_from_utils
+    ;; exemple/lib/utils.gfr:2:5
+    i32.const 46
+    i32.const 17
+    call $print
+    i32.const 12
+    i32.const 1
+    call $print
+  )
+  (func $u_hello_from_unit
+    ;; exemple/lib/unit.gfr:2:5
+    i32.const 63
+    i32.const 16
+    call $print
+    i32.const 12
+    i32.const 1
+    call $print
+  )
+  (export "main" (func $u_main))
+  (export "_start" (func $u_main))
+)
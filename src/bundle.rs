@@ -0,0 +1,191 @@
+use crate::grammar;
+use crate::parser::{float_text, Expr, Function, Program, Stmt};
+
+use std::fmt::Write;
+
+// Print the whole program as one gaufre source file, without imports :
+// the functions of every file followed by `main`.
+pub fn bundle_source(main: &Program, fns: &[Function]) -> String {
+    let mut out = String::new();
+    for f in fns {
+        print_function(&f.name, &f.params, &f.body, &mut out);
+        out.push('\n');
+    }
+    print_function(grammar::KW_MAIN, &[], &main.stmts, &mut out);
+    out
+}
+
+fn print_function(name: &str, params: &[String], body: &[Stmt], out: &mut String) {
+    writeln!(
+        out,
+        "{} {}{}{}{} {}",
+        grammar::KW_FN,
+        name,
+        grammar::LPAREN,
+        params.join(&format!("{} ", grammar::COMMA)),
+        grammar::RPAREN,
+        grammar::LBRACE
+    )
+    .unwrap();
+    for stmt in body {
+        print_stmt(stmt, 1, out);
+    }
+    writeln!(out, "{}", grammar::RBRACE).unwrap();
+}
+
+// one statement on its own line, indented by two spaces per block level
+fn print_stmt(stmt: &Stmt, depth: usize, out: &mut String) {
+    out.push_str(&"  ".repeat(depth));
+    match stmt {
+        Stmt::Log { args, .. } => {
+            out.push_str(grammar::KW_LOG);
+            print_args(args, out);
+            out.push_str(grammar::SEMICOLON);
+        }
+        Stmt::Call { name, args, .. } => {
+            write!(out, "{} {}", grammar::KW_CALL, name).unwrap();
+            print_args(args, out);
+            out.push_str(grammar::SEMICOLON);
+        }
+        Stmt::Let { name, value, .. } => {
+            write!(out, "{} {} {} ", grammar::KW_LET, name, grammar::ASSIGN).unwrap();
+            print_expr(value, out);
+            out.push_str(grammar::SEMICOLON);
+        }
+        Stmt::Return { value, .. } => {
+            out.push_str(grammar::KW_RETURN);
+            if let Some(value) = value {
+                out.push(' ');
+                print_expr(value, out);
+            }
+            out.push_str(grammar::SEMICOLON);
+        }
+        Stmt::Assign { name, value, .. } => {
+            write!(out, "{} {} ", name, grammar::ASSIGN).unwrap();
+            print_expr(value, out);
+            out.push_str(grammar::SEMICOLON);
+        }
+        Stmt::If { .. } => print_if(stmt, depth, out),
+        Stmt::While { cond, body, .. } => {
+            write!(out, "{} ", grammar::KW_WHILE).unwrap();
+            print_expr(cond, out);
+            out.push(' ');
+            print_block(body, depth, out);
+        }
+    }
+    out.push('\n');
+}
+
+// if cond { ... } else { ... }, an `else` block holding only an `if` is
+// printed as `else if`
+fn print_if(stmt: &Stmt, depth: usize, out: &mut String) {
+    let (arms, last) = stmt.if_arms();
+    for (i, (_, cond, then)) in arms.into_iter().enumerate() {
+        if i > 0 {
+            write!(out, " {} ", grammar::KW_ELSE).unwrap();
+        }
+        write!(out, "{} ", grammar::KW_IF).unwrap();
+        print_expr(cond, out);
+        out.push(' ');
+        print_block(then, depth, out);
+    }
+    if let Some(els) = last {
+        write!(out, " {} ", grammar::KW_ELSE).unwrap();
+        print_block(els, depth, out);
+    }
+}
+
+// { ... } : the statements one level deeper, the closing brace at depth
+fn print_block(stmts: &[Stmt], depth: usize, out: &mut String) {
+    if stmts.is_empty() {
+        return write!(out, "{}{}", grammar::LBRACE, grammar::RBRACE).unwrap();
+    }
+    writeln!(out, "{}", grammar::LBRACE).unwrap();
+    for stmt in stmts {
+        print_stmt(stmt, depth + 1, out);
+    }
+    out.push_str(&"  ".repeat(depth));
+    out.push_str(grammar::RBRACE);
+}
+
+// (expr, expr, ...)
+fn print_args(args: &[Expr], out: &mut String) {
+    out.push_str(grammar::LPAREN);
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            out.push_str(grammar::COMMA);
+            out.push(' ');
+        }
+        print_expr(arg, out);
+    }
+    out.push_str(grammar::RPAREN);
+}
+
+// string literal with its escape sequences
+fn print_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+const CMP_PRECEDENCE: u8 = 5;
+
+// precedence of an expression when printed, higher binds tighter
+fn precedence(e: &Expr) -> u8 {
+    match e {
+        Expr::Eq(..) | Expr::Ne(..) | Expr::Lt(..) | Expr::Le(..) | Expr::Gt(..) | Expr::Ge(..) => {
+            CMP_PRECEDENCE
+        }
+        Expr::Add(..) | Expr::Sub(..) => 10,
+        Expr::Mul(..) | Expr::Div(..) => 20,
+        _ => u8::MAX,
+    }
+}
+
+fn print_expr(e: &Expr, out: &mut String) {
+    let (a, op, b) = match e {
+        Expr::Str { value: s, .. } => return print_string(s, out),
+        Expr::Var { name, .. } => return out.push_str(name),
+        Expr::Int { value: n, .. } => return write!(out, "{}", n).unwrap(),
+        Expr::Float { value: x, .. } => return out.push_str(&float_text(*x)),
+        Expr::Bool { value: b, .. } => {
+            return out.push_str(if *b { grammar::KW_TRUE } else { grammar::KW_FALSE });
+        }
+        Expr::Add(a, b) => (a, grammar::PLUS, b),
+        Expr::Sub(a, b) => (a, grammar::MINUS, b),
+        Expr::Mul(a, b) => (a, grammar::STAR, b),
+        Expr::Div(a, b) => (a, grammar::SLASH, b),
+        Expr::Eq(a, b) => (a, grammar::EQ, b),
+        Expr::Ne(a, b) => (a, grammar::NE, b),
+        Expr::Lt(a, b) => (a, grammar::LT, b),
+        Expr::Le(a, b) => (a, grammar::LE, b),
+        Expr::Gt(a, b) => (a, grammar::GT, b),
+        Expr::Ge(a, b) => (a, grammar::GE, b),
+    };
+    // operators are left associative : the right operand needs parentheses
+    // when it has the same precedence. Comparisons cannot be chained, both
+    // operands need them.
+    let chained = precedence(e) == CMP_PRECEDENCE && precedence(a) == CMP_PRECEDENCE;
+    print_operand(a, precedence(a) < precedence(e) || chained, out);
+    write!(out, " {} ", op).unwrap();
+    print_operand(b, precedence(b) <= precedence(e), out);
+}
+
+fn print_operand(e: &Expr, parens: bool, out: &mut String) {
+    if parens {
+        out.push_str(grammar::LPAREN);
+        print_expr(e, out);
+        out.push_str(grammar::RPAREN);
+    } else {
+        print_expr(e, out);
+    }
+}
@@ -0,0 +1,230 @@
+use gaufre::Options;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "usage: gaufre [options] <root.gfr> [-o <out.wat>]
+       gaufre bundle [options] <root.gfr> [-o <bundle.gfr>]
+       gaufre --explain <code>
+
+options:
+  --import-root <dir>      directory of the imports starting with `/`
+  --define NAME[=VALUE]    define a symbol for `import \"x.gfr\" if NAME`, repeatable
+  -I <dir>                 import search directory, repeatable
+  --no-debug-comments      no `;; file:line:col` comments in the WAT
+  --fail-fast=false        report every independent semantic error, not only the first
+  --dump-ast               print the parsed program and imported functions, no output file
+  --dump-tokens            print the tokens of the root file, no output file
+  --emit wat|wasm          output format of a compilation (default: wat)
+  -o <file>                output file (default: <root>.wat or <root>.wasm, or stdout for bundle)
+  -h, --help               print this help
+  -V, --version            print the version";
+
+// output format of a compilation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Emit {
+    Wat,  // text format
+    Wasm, // binary module
+}
+
+impl Emit {
+    // extension of the output file when there is no -o
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Wat => "wat",
+            Self::Wasm => "wasm",
+        }
+    }
+}
+
+// what the command line asks for
+#[derive(Debug)]
+pub enum Command {
+    Help,
+    Version,
+    Explain(String),
+    Compile {
+        root: PathBuf,
+        out: Option<PathBuf>,
+        emit: Emit,
+    },
+    Bundle { root: PathBuf, out: Option<PathBuf> },
+    DumpAst { root: PathBuf },
+    DumpTokens { root: PathBuf },
+}
+
+#[derive(Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub options: Options,
+}
+
+// value of an option taking an argument, like `-o <file>`
+fn value(args: &mut impl Iterator<Item = String>, name: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("missing value after {}", name))
+}
+
+impl Cli {
+    // Parse the arguments, without the program name. The error is a message
+    // to print before the usage.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter().peekable();
+        let bundle = args.peek().map(String::as_str) == Some("bundle");
+        if bundle {
+            args.next();
+        }
+        let mut options = Options::default();
+        let mut positional = Vec::new();
+        let mut out = None;
+        let mut emit = Emit::Wat;
+        let mut dump_ast = false;
+        let mut dump_tokens = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    return Ok(Self {
+                        command: Command::Help,
+                        options,
+                    });
+                }
+                "-V" | "--version" => {
+                    return Ok(Self {
+                        command: Command::Version,
+                        options,
+                    });
+                }
+                "--explain" => {
+                    let code = value(&mut args, &arg)?;
+                    return Ok(Self {
+                        command: Command::Explain(code),
+                        options,
+                    });
+                }
+                "--emit" => {
+                    emit = match value(&mut args, &arg)?.as_str() {
+                        "wat" => Emit::Wat,
+                        "wasm" => Emit::Wasm,
+                        other => {
+                            return Err(format!("unknown format {}, expected wat or wasm", other));
+                        }
+                    }
+                }
+                "--dump-ast" => dump_ast = true,
+                "--dump-tokens" => dump_tokens = true,
+                "--no-debug-comments" => options.debug_comments = false,
+                "--fail-fast" | "--fail-fast=true" => options.fail_fast = true,
+                "--fail-fast=false" => options.fail_fast = false,
+                "-o" => out = Some(PathBuf::from(value(&mut args, &arg)?)),
+                "--import-root" => {
+                    options.import_root = Some(PathBuf::from(value(&mut args, &arg)?))
+                }
+                "--define" => {
+                    let def = value(&mut args, &arg)?;
+                    let (name, value) = def.split_once('=').unwrap_or((&def, ""));
+                    options.defines.insert(name.to_string(), value.to_string());
+                }
+                "-I" => options
+                    .search_paths
+                    .push(PathBuf::from(value(&mut args, &arg)?)),
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let root = PathBuf::from(positional.next().ok_or("missing <root.gfr>")?);
+        if let Some(extra) = positional.next() {
+            // the output file used to be a second positional argument
+            if bundle || out.is_some() {
+                return Err(format!("unexpected argument {}", extra));
+            }
+            out = Some(PathBuf::from(extra));
+        }
+        if let Some(extra) = positional.next() {
+            return Err(format!("unexpected argument {}", extra));
+        }
+        let command = if bundle {
+            Command::Bundle { root, out }
+        } else if dump_tokens {
+            Command::DumpTokens { root }
+        } else if dump_ast {
+            Command::DumpAst { root }
+        } else {
+            Command::Compile { root, out, emit }
+        };
+        Ok(Self { command, options })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, String> {
+        Cli::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn options_and_their_values() {
+        let cli = parse(&["--import-root", "src", "-I", "a", "in.gfr", "-I", "b", "-o", "x.wat"]);
+        let cli = cli.unwrap();
+        let Command::Compile { root, out, emit } = &cli.command else {
+            panic!("unexpected command {:?}", cli.command);
+        };
+        assert_eq!((root.to_str(), out.as_deref()), (Some("in.gfr"), Some("x.wat".as_ref())));
+        assert_eq!(*emit, Emit::Wat);
+        assert_eq!(cli.options.import_root, Some("src".into()));
+        assert_eq!(cli.options.search_paths, [PathBuf::from("a"), PathBuf::from("b")]);
+    }
+
+    #[test]
+    fn defines_with_and_without_value() {
+        let cli = parse(&["--define", "DEBUG", "--define", "LEVEL=2", "in.gfr"]).unwrap();
+        assert_eq!(cli.options.defines["DEBUG"], "");
+        assert_eq!(cli.options.defines["LEVEL"], "2");
+    }
+
+    #[test]
+    fn output_as_second_argument() {
+        let cli = parse(&["in.gfr", "out.wat"]).unwrap();
+        assert!(matches!(&cli.command, Command::Compile { out: Some(o), .. } if o == "out.wat"));
+        let cli = parse(&["bundle", "in.gfr"]).unwrap();
+        assert!(matches!(&cli.command, Command::Bundle { out: None, .. }));
+    }
+
+    #[test]
+    fn dumps_replace_the_compilation() {
+        let cli = parse(&["--dump-ast", "in.gfr"]).unwrap();
+        assert!(matches!(&cli.command, Command::DumpAst { root } if root == "in.gfr"));
+        let cli = parse(&["--dump-tokens", "in.gfr"]).unwrap();
+        assert!(matches!(&cli.command, Command::DumpTokens { root } if root == "in.gfr"));
+    }
+
+    #[test]
+    fn fail_fast_can_be_turned_off() {
+        assert!(parse(&["in.gfr"]).unwrap().options.fail_fast);
+        assert!(!parse(&["--fail-fast=false", "in.gfr"]).unwrap().options.fail_fast);
+        assert!(parse(&["--fail-fast=false", "--fail-fast", "in.gfr"]).unwrap().options.fail_fast);
+    }
+
+    #[test]
+    fn emit_format() {
+        let cli = parse(&["--emit", "wasm", "in.gfr"]).unwrap();
+        assert!(matches!(&cli.command, Command::Compile { emit: Emit::Wasm, .. }));
+        assert_eq!(Emit::Wasm.extension(), "wasm");
+        let e = parse(&["--emit", "wast", "in.gfr"]).unwrap_err();
+        assert_eq!(e, "unknown format wast, expected wat or wasm");
+    }
+
+    #[test]
+    fn usage_errors() {
+        for (args, message) in [
+            (&["--frobnicate", "in.gfr"][..], "unknown option --frobnicate"),
+            (&["in.gfr", "-o"], "missing value after -o"),
+            (&[], "missing <root.gfr>"),
+            (&["bundle", "in.gfr", "out.gfr"], "unexpected argument out.gfr"),
+            (&["a.gfr", "b.wat", "c"], "unexpected argument c"),
+        ] {
+            assert_eq!(parse(args).unwrap_err(), message, "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,839 @@
+use crate::Options;
+use crate::error::CompileError;
+use crate::grammar;
+use crate::lexer::Pos;
+use crate::parser::{float_text, Expr, Function, Program, Stmt, Value};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+const PAGE_SIZE: usize = 65536; // size of a wasm memory page
+
+// memory layout : the start of the memory is used by $print, the string
+// constants come after
+const IOVEC: usize = 0; // iovec (ptr, len) given to fd_write
+const NWRITTEN: usize = 8; // number of bytes written, returned by fd_write
+const NEWLINE: usize = 12; // "\n" printed at the end of each log
+const ITOA_BUF: usize = 16; // digits written by $itoa, "-2147483648" fits in 11 bytes
+const ITOA_END: usize = ITOA_BUF + 12; // $itoa writes backward from here
+const DATA_START: usize = ITOA_END; // first string constant
+const STDOUT: i32 = 1;
+
+// WAT identifier of a user function : the prefix keeps `fn print()` or
+// `fn fd_write()` apart from the helpers and the imports
+fn func_id(name: &str) -> String {
+    format!("$u_{}", name)
+}
+
+// WAT module under construction
+struct Codegen {
+    data: Vec<(usize, String)>,      // string constants : (offset in memory, bytes)
+    data_end: usize,                 // first free byte after the constants
+    strings: HashMap<String, usize>, // offset of each constant, equal strings share it
+    uses_itoa: bool,                 // $itoa and $print_i32 must be emitted
+    bool_text: Option<usize>,        // offset of "truefalse", used by $print_bool
+    returns: HashSet<String>,        // functions with an i32 result
+    debug_comments: bool,            // `;; file:line:col` before each statement
+    // locals of the function being emitted. The visible variables, innermost
+    // block last : name -> (WAT local, holds a bool)
+    scopes: Vec<HashMap<String, (String, bool)>>,
+    locals: Vec<String>,  // WAT locals declared after the parameters
+    bool_lets: Vec<bool>, // kind of each `let` of the function, see BoolLets
+    next_let: usize,      // index in bool_lets of the next `let` emitted
+}
+
+impl Codegen {
+    fn new(fns: &[Function], options: &Options) -> Self {
+        Self {
+            data: Vec::new(),
+            data_end: DATA_START,
+            strings: HashMap::new(),
+            uses_itoa: false,
+            bool_text: None,
+            returns: fns
+                .iter()
+                .filter(|f| f.returns_value())
+                .map(|f| f.name.clone())
+                .collect(),
+            debug_comments: options.debug_comments,
+            scopes: Vec::new(),
+            locals: Vec::new(),
+            bool_lets: Vec::new(),
+            next_let: 0,
+        }
+    }
+
+    // store a string constant in memory and return its (offset, length), a
+    // string already stored is not stored again
+    fn add_data(&mut self, s: &str) -> (usize, usize) {
+        if let Some(&offset) = self.strings.get(s) {
+            return (offset, s.len());
+        }
+        let offset = self.data_end;
+        self.strings.insert(s.to_string(), offset);
+        if !s.is_empty() {
+            self.data.push((offset, s.to_string()));
+        }
+        self.data_end += s.len();
+        (offset, s.len())
+    }
+
+    // WAT local of a visible variable, and whether it holds a bool
+    fn var(&self, name: &str) -> &(String, bool) {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .expect("variables are checked by the resolver")
+    }
+
+    fn local(&self, name: &str) -> &str {
+        &self.var(name).0
+    }
+
+    // the value of e is a bool, printed as true or false
+    fn is_bool(&self, e: &Expr) -> bool {
+        match e {
+            Expr::Var { name, .. } => self.var(name).1,
+            _ => e.is_bool(),
+        }
+    }
+
+    // WAT local for a `let` in the innermost block. Locals are function wide
+    // in wasm : a `let` shadowing a variable of an enclosing block gets its
+    // own local `$name.N`, so the outer variable keeps its value after the
+    // block. A local of a block already closed is reused.
+    fn declare(&mut self, name: &str, is_bool: bool) -> String {
+        let scope = self.scopes.last_mut().expect("a block is open");
+        if let Some((local, kind)) = scope.get_mut(name) {
+            *kind = is_bool;
+            return local.clone(); // shadowing in the same block
+        }
+        let visible: HashSet<&String> = self
+            .scopes
+            .iter()
+            .flat_map(|s| s.values().map(|(local, _)| local))
+            .collect();
+        let mut n = 0;
+        let local = loop {
+            let local = if n == 0 {
+                name.to_string()
+            } else {
+                format!("{}.{}", name, n)
+            };
+            if !visible.contains(&local) {
+                break local;
+            }
+            n += 1;
+        };
+        if !self.locals.contains(&local) {
+            self.locals.push(local.clone());
+        }
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), (local.clone(), is_bool));
+        local
+    }
+
+    // statements of a nested block, with their own variables
+    fn emit_block(&mut self, stmts: &[Stmt], out: &mut String) -> Result<(), CompileError> {
+        self.scopes.push(HashMap::new());
+        for stmt in stmts {
+            self.emit_stmt(stmt, out)?;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn emit_pos(&self, pos: &Pos, out: &mut String) {
+        if self.debug_comments {
+            writeln!(out, "    ;; {}:{}:{}", pos.file, pos.line, pos.col).unwrap();
+        }
+    }
+
+    fn emit_stmt(&mut self, stmt: &Stmt, out: &mut String) -> Result<(), CompileError> {
+        self.emit_pos(stmt.pos(), out);
+        match stmt {
+            Stmt::Log { args, .. } => {
+                // the arguments are printed one after the other, then the newline
+                for arg in args {
+                    // known at compile time : printed as a string constant
+                    let text = match arg.const_eval() {
+                        Some(Value::Str(s)) => s,
+                        Some(Value::Int(n)) => n.to_string(),
+                        Some(Value::Float(x)) => float_text(x), // 1.0 rather than 1
+                        Some(Value::Bool(b)) => b.to_string(),
+                        None if self.is_bool(arg) => {
+                            // comparison or bool variable computed at runtime : true or false
+                            self.emit_expr(arg, out)?;
+                            writeln!(out, "    call $print_bool").unwrap();
+                            if self.bool_text.is_none() {
+                                self.bool_text = Some(self.add_data("truefalse").0);
+                            }
+                            continue;
+                        }
+                        None => {
+                            // computed at runtime and converted to decimal
+                            self.emit_expr(arg, out)?;
+                            writeln!(out, "    call $print_i32").unwrap();
+                            self.uses_itoa = true;
+                            continue;
+                        }
+                    };
+                    let (offset, len) = self.add_data(&text);
+                    emit_print(offset, len, out);
+                }
+                emit_print(NEWLINE, 1, out);
+            }
+            Stmt::Call { name, args, .. } => {
+                // the arguments are pushed in order, one per parameter
+                for arg in args {
+                    self.emit_expr(arg, out)?;
+                }
+                writeln!(out, "    call {}", func_id(name)).unwrap();
+                // the result of a call statement is not used
+                if self.returns.contains(name) {
+                    writeln!(out, "    drop").unwrap();
+                }
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.emit_expr(value, out)?;
+                }
+                writeln!(out, "    return").unwrap();
+            }
+            Stmt::If { .. } => {
+                // each `else if` is an `if` in the `else` of the previous one,
+                // all of them are closed at the end
+                let (arms, last) = stmt.if_arms();
+                for (i, (pos, cond, then)) in arms.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(out, "    else").unwrap();
+                        self.emit_pos(pos, out);
+                    }
+                    self.emit_expr(cond, out)?;
+                    writeln!(out, "    if").unwrap();
+                    self.emit_block(then, out)?;
+                }
+                if let Some(els) = last {
+                    writeln!(out, "    else").unwrap();
+                    self.emit_block(els, out)?;
+                }
+                for _ in &arms {
+                    writeln!(out, "    end").unwrap();
+                }
+            }
+            Stmt::While { cond, body, .. } => {
+                // block { loop { exit when !cond ; body ; next iteration } }
+                writeln!(out, "    block").unwrap();
+                writeln!(out, "    loop").unwrap();
+                self.emit_expr(cond, out)?;
+                writeln!(out, "    i32.eqz").unwrap();
+                writeln!(out, "    br_if 1").unwrap();
+                self.emit_block(body, out)?;
+                writeln!(out, "    br 0").unwrap();
+                writeln!(out, "    end").unwrap();
+                writeln!(out, "    end").unwrap();
+            }
+            Stmt::Let { name, value, .. } => {
+                // the value is computed before the new variable is visible
+                self.emit_expr(value, out)?;
+                let is_bool = self.bool_lets[self.next_let];
+                self.next_let += 1;
+                let local = self.declare(name, is_bool);
+                writeln!(out, "    local.set ${}", local).unwrap();
+            }
+            Stmt::Assign { name, value, .. } => {
+                self.emit_expr(value, out)?;
+                writeln!(out, "    local.set ${}", self.local(name)).unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    // emit the instructions leaving the i32 value of e on the stack
+    fn emit_expr(&self, e: &Expr, out: &mut String) -> Result<(), CompileError> {
+        // an operation on constants is folded : `2 * 3` is one i32.const 6
+        if e.is_constant() {
+            match e.const_eval() {
+                Some(Value::Int(n)) => {
+                    writeln!(out, "    i32.const {}", n).unwrap();
+                    return Ok(());
+                }
+                Some(Value::Bool(b)) => {
+                    writeln!(out, "    i32.const {}", b as i32).unwrap();
+                    return Ok(());
+                }
+                _ => {} // strings and floats are rejected below
+            }
+        }
+        let (a, b, instr) = match e {
+            Expr::Int { value: n, .. } => {
+                writeln!(out, "    i32.const {}", n).unwrap();
+                return Ok(());
+            }
+            Expr::Bool { value: b, .. } => {
+                writeln!(out, "    i32.const {}", *b as i32).unwrap();
+                return Ok(());
+            }
+            Expr::Str { .. } => {
+                return Err(CompileError::Unsupported {
+                    what: "strings in arithmetic expressions".into(),
+                });
+            }
+            Expr::Float { .. } => {
+                return Err(CompileError::Unsupported {
+                    what: "floats mixed with integers, or computed at runtime".into(),
+                });
+            }
+            Expr::Var { name, .. } => {
+                writeln!(out, "    local.get ${}", self.local(name)).unwrap();
+                return Ok(());
+            }
+            Expr::Add(a, b) => (a, b, "i32.add"),
+            Expr::Sub(a, b) => (a, b, "i32.sub"),
+            Expr::Mul(a, b) => (a, b, "i32.mul"),
+            Expr::Div(a, b) => {
+                // would trap at runtime
+                if b.const_eval() == Some(Value::Int(0)) {
+                    return Err(CompileError::DivisionByZero { pos: b.pos().clone() });
+                }
+                (a, b, "i32.div_s")
+            }
+            Expr::Eq(a, b) => (a, b, "i32.eq"),
+            Expr::Ne(a, b) => (a, b, "i32.ne"),
+            Expr::Lt(a, b) => (a, b, "i32.lt_s"),
+            Expr::Le(a, b) => (a, b, "i32.le_s"),
+            Expr::Gt(a, b) => (a, b, "i32.gt_s"),
+            Expr::Ge(a, b) => (a, b, "i32.ge_s"),
+        };
+        self.emit_expr(a, out)?;
+        self.emit_expr(b, out)?;
+        writeln!(out, "    {}", instr).unwrap();
+        Ok(())
+    }
+}
+
+// Which variables hold a bool, so `let b = 1 < 2; log(b);` prints true. A
+// variable is a bool when its `let` value and every assignment to it are
+// comparisons, bools or bool variables. The result has one entry per `let`
+// of the function, in the order the codegen visits them. Assignments later
+// in the function, or in a loop, change the kind of a variable already used :
+// the walk is repeated until the kinds no longer change.
+struct BoolLets<'a> {
+    prev: &'a [bool], // kinds found by the previous walk, all true at first
+    // visible variables : name -> index of its `let`, None for a parameter
+    scopes: Vec<HashMap<&'a str, Option<usize>>>,
+    lets: Vec<bool>, // kinds found by this walk
+}
+
+impl<'a> BoolLets<'a> {
+    fn of(params: &'a [String], body: &'a [Stmt]) -> Vec<bool> {
+        let mut kinds = Vec::new();
+        loop {
+            let mut walk = BoolLets {
+                prev: &kinds,
+                scopes: vec![params.iter().map(|p| (p.as_str(), None)).collect()],
+                lets: Vec::new(),
+            };
+            walk.stmts(body);
+            if walk.lets == kinds {
+                return kinds;
+            }
+            kinds = walk.lets;
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes.iter().rev().find_map(|s| s.get(name).copied()).flatten()
+    }
+
+    fn is_bool(&self, e: &Expr) -> bool {
+        match e {
+            Expr::Var { name, .. } => self
+                .lookup(name)
+                .is_some_and(|i| self.prev.get(i).copied().unwrap_or(true)),
+            _ => e.is_bool(),
+        }
+    }
+
+    fn block(&mut self, stmts: &'a [Stmt]) {
+        self.scopes.push(HashMap::new());
+        self.stmts(stmts);
+        self.scopes.pop();
+    }
+
+    fn stmts(&mut self, stmts: &'a [Stmt]) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Let { name, value, .. } => {
+                    let is_bool = self.is_bool(value);
+                    self.lets.push(is_bool);
+                    let i = self.lets.len() - 1;
+                    self.scopes.last_mut().unwrap().insert(name, Some(i));
+                }
+                Stmt::Assign { name, value, .. } => {
+                    if let Some(i) = self.lookup(name)
+                        && !self.is_bool(value)
+                    {
+                        self.lets[i] = false;
+                    }
+                }
+                Stmt::If { .. } => {
+                    let (arms, last) = stmt.if_arms();
+                    for (_, _, then) in arms {
+                        self.block(then);
+                    }
+                    if let Some(els) = last {
+                        self.block(els);
+                    }
+                }
+                Stmt::While { body, .. } => self.block(body),
+                Stmt::Log { .. } | Stmt::Call { .. } | Stmt::Return { .. } => {}
+            }
+        }
+    }
+}
+
+// escape a string for a WAT data segment
+fn wat_string(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        match b {
+            b'"' | b'\\' => write!(out, "\\{:02x}", b).unwrap(),
+            0x20..=0x7E => out.push(b as char),
+            _ => write!(out, "\\{:02x}", b).unwrap(),
+        }
+    }
+    out
+}
+
+// (func $name ...) for a function and its body
+fn emit_function(
+    cg: &mut Codegen,
+    name: &str,
+    params: &[String],
+    body: &[Stmt],
+    out: &mut String,
+) -> Result<(), CompileError> {
+    write!(out, "  (func {}", func_id(name)).unwrap();
+    for param in params {
+        write!(out, " (param ${} i32)", param).unwrap();
+    }
+    let result = cg.returns.contains(name);
+    if result {
+        out.push_str(" (result i32)");
+    }
+    out.push('\n');
+    // the parameters and the variables of the function body share a scope : a
+    // `let` of a parameter name reuses the parameter
+    cg.scopes = vec![params.iter().map(|p| (p.clone(), (p.clone(), false))).collect()];
+    cg.locals.clear();
+    cg.bool_lets = BoolLets::of(params, body);
+    cg.next_let = 0;
+    let mut code = String::new();
+    for stmt in body {
+        cg.emit_stmt(stmt, &mut code)?;
+    }
+    // one i32 local per variable, declared before the instructions
+    for local in &cg.locals {
+        writeln!(out, "    (local ${} i32)", local).unwrap();
+    }
+    out.push_str(&code);
+    // reaching the end of a function with a result, without `return`, traps
+    if result {
+        writeln!(out, "    unreachable").unwrap();
+    }
+    out.push_str("  )\n");
+    Ok(())
+}
+
+// Build the WAT text of the whole program : `main` and the other functions
+pub fn emit_module(
+    root: &Program,
+    fns: &[Function],
+    options: &Options,
+) -> Result<String, CompileError> {
+    let mut cg = Codegen::new(fns, options);
+    let mut funcs = String::new();
+    emit_function(&mut cg, grammar::KW_MAIN, &[], &root.stmts, &mut funcs)?;
+    for f in fns {
+        emit_function(&mut cg, &f.name, &f.params, &f.body, &mut funcs)?;
+    }
+
+    let pages = cg.data_end.div_ceil(PAGE_SIZE).max(1);
+    let mut out = String::new();
+    out.push_str("(module\n");
+    out.push_str(
+        "  (import \"wasi_snapshot_preview1\" \"fd_write\" \
+         (func $fd_write (param i32 i32 i32 i32) (result i32)))\n",
+    );
+    writeln!(out, "  (memory (export \"memory\") {})", pages).unwrap();
+    writeln!(out, "  (data (i32.const {}) \"\\0a\")", NEWLINE).unwrap();
+    for (offset, s) in &cg.data {
+        writeln!(out, "  (data (i32.const {}) \"{}\")", offset, wat_string(s)).unwrap();
+    }
+    emit_print_helper(&mut out);
+    if cg.uses_itoa {
+        emit_itoa_helper(&mut out);
+    }
+    if let Some(offset) = cg.bool_text {
+        emit_print_bool_helper(offset, &mut out);
+    }
+    out.push_str(&funcs);
+    let main = func_id(grammar::KW_MAIN);
+    writeln!(out, "  (export \"main\" (func {}))", main).unwrap();
+    // entry point of a WASI command
+    writeln!(out, "  (export \"_start\" (func {}))", main).unwrap();
+    out.push_str(")\n");
+    Ok(out)
+}
+
+// print the bytes [offset, offset + len[ of the memory
+fn emit_print(offset: usize, len: usize, out: &mut String) {
+    writeln!(out, "    i32.const {}", offset).unwrap();
+    writeln!(out, "    i32.const {}", len).unwrap();
+    writeln!(out, "    call $print").unwrap();
+}
+
+// $print(ptr, len) : write the string on stdout with fd_write
+fn emit_print_helper(out: &mut String) {
+    out.push_str("  (func $print (param $ptr i32) (param $len i32)\n");
+    writeln!(out, "    (i32.store (i32.const {}) (local.get $ptr))", IOVEC).unwrap();
+    writeln!(out, "    (i32.store (i32.const {}) (local.get $len))", IOVEC + 4).unwrap();
+    writeln!(
+        out,
+        "    (drop (call $fd_write (i32.const {}) (i32.const {}) (i32.const 1) (i32.const {})))",
+        STDOUT, IOVEC, NWRITTEN
+    )
+    .unwrap();
+    out.push_str("  )\n");
+}
+
+// $itoa(n) : write the decimal digits of n at the end of the itoa buffer and
+// return their count. The digits are taken from the remainder of the signed
+// value, which is never negated so i32::MIN works too.
+// $print_i32(n) : print the decimal representation of n
+fn emit_itoa_helper(out: &mut String) {
+    out.push_str("  (func $itoa (param $n i32) (result i32)\n");
+    out.push_str("    (local $p i32) (local $d i32) (local $neg i32)\n");
+    writeln!(out, "    (local.set $p (i32.const {}))", ITOA_END).unwrap();
+    out.push_str("    (local.set $neg (i32.lt_s (local.get $n) (i32.const 0)))\n");
+    out.push_str("    (loop $digits\n");
+    out.push_str("      (local.set $d (i32.rem_s (local.get $n) (i32.const 10)))\n");
+    out.push_str("      (if (i32.lt_s (local.get $d) (i32.const 0))\n");
+    out.push_str("        (then (local.set $d (i32.sub (i32.const 0) (local.get $d)))))\n");
+    out.push_str("      (local.set $p (i32.sub (local.get $p) (i32.const 1)))\n");
+    out.push_str("      (i32.store8 (local.get $p) (i32.add (local.get $d) (i32.const 48)))\n");
+    out.push_str("      (local.set $n (i32.div_s (local.get $n) (i32.const 10)))\n");
+    out.push_str("      (br_if $digits (local.get $n)))\n");
+    out.push_str("    (if (local.get $neg)\n");
+    out.push_str("      (then\n");
+    out.push_str("        (local.set $p (i32.sub (local.get $p) (i32.const 1)))\n");
+    out.push_str("        (i32.store8 (local.get $p) (i32.const 45))))\n");
+    writeln!(out, "    (i32.sub (i32.const {}) (local.get $p))", ITOA_END).unwrap();
+    out.push_str("  )\n");
+    out.push_str("  (func $print_i32 (param $n i32)\n");
+    out.push_str("    (local $len i32)\n");
+    out.push_str("    (local.set $len (call $itoa (local.get $n)))\n");
+    writeln!(
+        out,
+        "    (call $print (i32.sub (i32.const {}) (local.get $len)) (local.get $len))",
+        ITOA_END
+    )
+    .unwrap();
+    out.push_str("  )\n");
+}
+
+// $print_bool(b) : print "true" when b is not 0, "false" otherwise, both
+// taken from the "truefalse" string at offset
+fn emit_print_bool_helper(offset: usize, out: &mut String) {
+    out.push_str("  (func $print_bool (param $b i32)\n");
+    out.push_str("    (if (local.get $b)\n");
+    writeln!(out, "      (then (call $print (i32.const {}) (i32.const 4)))", offset).unwrap();
+    writeln!(out, "      (else (call $print (i32.const {}) (i32.const 5))))", offset + 4).unwrap();
+    out.push_str("  )\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::wat_vm::Vm;
+
+    // the exact WAT of the statements, without the position comments
+    fn no_comments() -> Options {
+        Options {
+            debug_comments: false,
+            ..Options::default()
+        }
+    }
+
+    fn wat(src: &str) -> String {
+        let (_, prog, _) = Parser::new(Lexer::new(src)).unwrap().parse_main_program().unwrap();
+        emit_module(&prog, &[], &no_comments()).unwrap()
+    }
+
+    #[test]
+    fn log_prints_each_argument_then_a_newline() {
+        let wat = wat("fn main() {\n  log(\"hé\", \"x\");\n}");
+        assert!(wat.contains("  (data (i32.const 28) \"h\\c3\\a9\")\n"));
+        assert!(wat.contains("  (data (i32.const 31) \"x\")\n"));
+        let print = |offset, len| {
+            format!("    i32.const {}\n    i32.const {}\n    call $print\n", offset, len)
+        };
+        let body = [print(28, 3), print(31, 1), print(NEWLINE, 1)].concat();
+        assert!(wat.contains(&body), "{}", wat);
+    }
+
+    #[test]
+    fn constant_expressions_are_logged_as_text() {
+        let wat = wat("fn main() {\n  log(1 + 2 + 3, \"a\" + \"b\");\n}");
+        assert!(wat.contains("  (data (i32.const 28) \"6\")\n  (data (i32.const 29) \"ab\")\n"));
+    }
+
+    #[test]
+    fn statements_are_preceded_by_their_position() {
+        let src = "fn main() {\n  let x = 1;\n  if x {\n    log(x);\n  }\n}";
+        let (_, prog, _) = Parser::new(Lexer::new(src)).unwrap().parse_main_program().unwrap();
+        let wat = emit_module(&prog, &[], &Options::default()).unwrap();
+        let expected = "    ;; <stdin>:2:3\n    i32.const 1\n    local.set $x\n    \
+                        ;; <stdin>:3:3\n    local.get $x\n    if\n    ;; <stdin>:4:5\n";
+        assert!(wat.contains(expected), "{}", wat);
+        assert_eq!(Vm::run_main(&wat), "1\n");
+        assert!(!self::wat(src).contains(";;"));
+    }
+
+    #[test]
+    fn equal_strings_are_stored_once() {
+        let wat = wat("fn main() {\n  log(\"ab\", \"cd\", \"a\" + \"b\");\n  log(\"cd\");\n}");
+        let data = "  (data (i32.const 28) \"ab\")\n  (data (i32.const 30) \"cd\")\n  (func";
+        assert!(wat.contains(data), "{}", wat);
+        // "ab" and "cd" are each printed twice from the same offset
+        let print = |at| format!("    i32.const {}\n    i32.const 2\n    call $print\n", at);
+        assert_eq!(wat.matches(&print(28)).count(), 2, "{}", wat);
+        assert_eq!(wat.matches(&print(30)).count(), 2, "{}", wat);
+    }
+
+    fn error(src: &str) -> CompileError {
+        let (_, prog, _) = Parser::new(Lexer::new(src)).unwrap().parse_main_program().unwrap();
+        emit_module(&prog, &[], &no_comments()).unwrap_err()
+    }
+
+    #[test]
+    fn computed_integers_are_printed_at_runtime() {
+        let src = "fn main() {\n  let x = 6;\n  log(x * 7, \" \", 0 - x);\n}";
+        assert_eq!(Vm::run_main(&wat(src)), "42 -6\n");
+        // $itoa is only emitted when needed
+        assert!(!wat("fn main() {\n  log(1);\n}").contains("$itoa"));
+    }
+
+    #[test]
+    fn itoa_of_extreme_and_small_values() {
+        let mut vm = Vm::new(&wat("fn main() {\n  let x = 1;\n  log(x);\n}"));
+        for n in [i32::MIN, i32::MAX, -10, -1, 0, 1, 7, 9, 10] {
+            let len = vm.call("$itoa", &[n])[0] as usize;
+            let digits = &vm.memory[ITOA_END - len..ITOA_END];
+            assert_eq!(std::str::from_utf8(digits).unwrap(), n.to_string());
+        }
+    }
+
+    #[test]
+    fn constant_division_by_zero_is_an_error() {
+        // reported at the divisor
+        for (src, col) in [
+            ("log(1 / 0);", 11),
+            ("log(1 / (2 - 2));", 12),
+            ("log(2147483647 + 1 + 4 / 0);", 28),
+        ] {
+            let e = error(&format!("fn main() {{\n  {}\n}}", src));
+            let CompileError::DivisionByZero { pos } = &e else {
+                panic!("{}: unexpected error {:?}", src, e);
+            };
+            assert_eq!((pos.line, pos.col), (2, col), "{}", src);
+        }
+        let e = error("fn main() {\n  log(1 / 0);\n}");
+        assert_eq!(e.to_string(), "<stdin>:2:11: error[E0011]: division by zero");
+    }
+
+    #[test]
+    fn constant_operands_are_folded() {
+        let src = "fn main() {\n  let x = 2;\n  log(x * (2 + 3), x < 1 + 1);\n}";
+        let wat = wat(src);
+        assert!(wat.contains("    local.get $x\n    i32.const 5\n    i32.mul\n"), "{}", wat);
+        assert!(wat.contains("    local.get $x\n    i32.const 2\n    i32.lt_s\n"), "{}", wat);
+        let main = &wat[wat.find("(func $u_main").unwrap()..];
+        assert!(!main.contains("i32.add"), "{}", wat);
+        assert_eq!(Vm::run_main(&wat), "10false\n");
+        // an overflow is not constant, it wraps at runtime
+        let wat = self::wat("fn main() {\n  let x = 1;\n  x = 2147483647 + 1;\n}");
+        assert!(wat.contains("    i32.const 1\n    i32.add\n"), "{}", wat);
+    }
+
+    #[test]
+    fn constant_floats_are_logged_with_a_point() {
+        let wat = wat("fn main() {\n  log(0.5 + 0.5);\n}");
+        assert!(wat.contains("  (data (i32.const 28) \"1.0\")\n"), "{}", wat);
+    }
+
+    #[test]
+    fn variables_are_function_locals() {
+        let wat = wat("fn main() {\n  let x = 1;\n  x = x * 2;\n  let x = 3;\n  log(\"a\");\n}");
+        let body = "  (func $u_main\n    (local $x i32)\n    i32.const 1\n    local.set $x\n    \
+                    local.get $x\n    i32.const 2\n    i32.mul\n    local.set $x\n    \
+                    i32.const 3\n    local.set $x\n";
+        assert!(wat.contains(body), "{}", wat);
+    }
+
+    #[test]
+    fn parameters_are_wasm_params() {
+        let (_, prog, _) =
+            Parser::new(Lexer::new("fn main() {}")).unwrap().parse_main_program().unwrap();
+        let lib = "fn f(a, b) {\n  let a = b;\n}";
+        let (_, fns) = Parser::new(Lexer::new(lib)).unwrap().parse_sub_functions().unwrap();
+        let wat = emit_module(&prog, &fns, &no_comments()).unwrap();
+        // the `let` of a parameter reuses it, no local is declared
+        let f = "  (func $u_f (param $a i32) (param $b i32)\n    local.get $b\n";
+        assert!(wat.contains(f), "{}", wat);
+    }
+
+    #[test]
+    fn call_passes_the_arguments_in_order() {
+        let (_, prog, _) = Parser::new(Lexer::new("fn main() {\n  call sub(10, 3);\n}"))
+            .unwrap()
+            .parse_main_program()
+            .unwrap();
+        let lib = "fn sub(a, b) {\n  log(a - b);\n}";
+        let (_, fns) = Parser::new(Lexer::new(lib)).unwrap().parse_sub_functions().unwrap();
+        assert_eq!(Vm::run_main(&emit_module(&prog, &fns, &no_comments()).unwrap()), "7\n");
+    }
+
+    #[test]
+    fn user_functions_do_not_clash_with_the_helpers() {
+        let (_, prog, _) = Parser::new(Lexer::new("fn main() {\n  call print(1);\n}"))
+            .unwrap()
+            .parse_main_program()
+            .unwrap();
+        let lib = "fn print(n) {\n  log(n + 1);\n  call fd_write();\n}\nfn fd_write() {}";
+        let (_, fns) = Parser::new(Lexer::new(lib)).unwrap().parse_sub_functions().unwrap();
+        let wat = emit_module(&prog, &fns, &no_comments()).unwrap();
+        assert!(crate::wasm::assemble(&wat).is_ok(), "{}", wat);
+        assert_eq!(Vm::run_main(&wat), "2\n");
+    }
+
+    #[test]
+    fn function_with_a_result() {
+        let (_, prog, _) = Parser::new(Lexer::new("fn main() {\n  call f(5);\n}"))
+            .unwrap()
+            .parse_main_program()
+            .unwrap();
+        let lib = "fn f(a) {\n  log(a);\n  return a;\n  log(0);\n}";
+        let (_, fns) = Parser::new(Lexer::new(lib)).unwrap().parse_sub_functions().unwrap();
+        let wat = emit_module(&prog, &fns, &no_comments()).unwrap();
+        assert!(wat.contains("  (func $u_f (param $a i32) (result i32)\n"), "{}", wat);
+        // the result of the call statement is dropped
+        assert!(wat.contains("    call $u_f\n    drop\n"), "{}", wat);
+        assert_eq!(Vm::run_main(&wat), "5\n");
+    }
+
+    #[test]
+    fn if_else_is_a_wasm_if() {
+        let src = "fn main() {\n  let x = 0;\n  if x {\n    x = 1;\n  } else {\n    x = 2;\n  }\n}";
+        let body = "    local.get $x\n    if\n    i32.const 1\n    local.set $x\n    else\n    \
+                    i32.const 2\n    local.set $x\n    end\n";
+        let out = wat(src);
+        assert!(out.contains(body), "{}", out);
+        // without else
+        let out = wat("fn main() {\n  if 1 {\n    log(\"a\");\n  }\n}");
+        assert!(!out.contains("    else\n"), "{}", out);
+    }
+
+    #[test]
+    fn else_if_chain_runs_one_arm() {
+        let arms = "  if n - 1 {\n    if n - 2 {\n      log(\"other\");\n    } else {\n      \
+                    log(\"two\");\n    }\n  } else if 1 {\n    log(\"one\");\n  }\n";
+        for (n, printed) in [(1, "one\n"), (2, "two\n"), (3, "other\n")] {
+            let src = format!("fn main() {{\n  let n = {};\n{}}}", n, arms);
+            assert_eq!(Vm::run_main(&wat(&src)), printed);
+        }
+    }
+
+    #[test]
+    fn deepest_expression_and_long_else_if_chain() {
+        use crate::parser::MAX_NESTING;
+        // x + x + ... : a tree as deep as the parser allows
+        let sum = format!("x{}", " + x".repeat(MAX_NESTING - 1));
+        let arms: String = (0..3 * MAX_NESTING)
+            .map(|i| format!(" else if n == {} {{\n    log({});\n  }}", i, i))
+            .collect();
+        let src = format!(
+            "fn main() {{\n  let x = 1;\n  let n = 300;\n  log({});\n  if 0 {{}}{}\n}}",
+            sum, arms
+        );
+        assert_eq!(Vm::run_main(&wat(&src)), format!("{}\n300\n", MAX_NESTING));
+    }
+
+    #[test]
+    fn locals_declared_in_blocks() {
+        let wat = wat("fn main() {\n  if 1 {\n    let y = 2;\n    log(y);\n  }\n}");
+        assert!(wat.contains("  (func $u_main\n    (local $y i32)\n"), "{}", wat);
+    }
+
+    #[test]
+    fn shadowing_let_in_a_block_has_its_own_local() {
+        // let in a while in an if : the x of main keeps its value after the blocks
+        let src = "fn main() {\n  let x = 1;\n  if x {\n    let n = 2;\n    while n {\n      \
+                   let x = n * 10;\n      log(x);\n      n = n - 1;\n    }\n    log(x);\n  }\n  \
+                   if x {\n    let x = 5;\n    x = x + 1;\n    log(x);\n  }\n  log(x);\n}";
+        let wat = wat(src);
+        let locals = "    (local $x i32)\n    (local $n i32)\n    (local $x.1 i32)\n";
+        assert!(wat.contains(locals), "{}", wat);
+        assert!(crate::wasm::assemble(&wat).is_ok(), "{}", wat);
+        assert_eq!(Vm::run_main(&wat), "20\n10\n1\n6\n1\n");
+    }
+
+    #[test]
+    fn while_loop_counts_down() {
+        let src = "fn main() {\n  let n = 3;\n  while n {\n    log(n);\n    n = n - 1;\n  }\n  \
+                   log(\"done\");\n}";
+        assert_eq!(Vm::run_main(&wat(src)), "3\n2\n1\ndone\n");
+        // the condition is checked before the first iteration
+        let src = "fn main() {\n  while 0 {\n    log(\"never\");\n  }\n}";
+        assert_eq!(Vm::run_main(&wat(src)), "");
+    }
+
+    #[test]
+    fn comparisons_are_printed_as_booleans() {
+        let src = "fn main() {\n  let n = 2;\n  \
+                   log(n < 3, n <= 1, n == 2, n != 2, n > 1, n >= 3);\n}";
+        assert_eq!(Vm::run_main(&wat(src)), "truefalsetruefalsetruefalse\n");
+    }
+
+    #[test]
+    fn constant_booleans_are_logged_as_text() {
+        let out = wat("fn main() {\n  log(true, 1 < 2, false == true);\n}");
+        assert!(!out.contains("$print_bool"), "{}", out);
+        let data = "  (data (i32.const 28) \"true\")\n  (data (i32.const 32) \"false\")\n  (func";
+        assert!(out.contains(data), "{}", out);
+        let src = "fn main() {\n  let b = false;\n  if b == false {\n    log(\"not b\");\n  }\n}";
+        assert_eq!(Vm::run_main(&wat(src)), "not b\n");
+    }
+
+    #[test]
+    fn boolean_variables_are_logged_as_text() {
+        // a comparison, a literal, a copy and a parameter kept as an integer
+        let src = "fn main() {\n  let n = 2;\n  let b = n < 3;\n  let c = b;\n  log(b, c);\n  \
+                   b = false;\n  log(b, n);\n}";
+        assert_eq!(Vm::run_main(&wat(src)), "truetrue\nfalse2\n");
+        // an integer assigned later, in a loop : b is an integer from its `let`
+        let src = "fn main() {\n  let n = 2;\n  let b = true;\n  while n {\n    log(b);\n    \
+                   b = n;\n    n = n - 1;\n  }\n}";
+        assert_eq!(Vm::run_main(&wat(src)), "1\n2\n");
+        // a shadowing let has its own kind
+        let src = "fn main() {\n  let x = 1;\n  if x {\n    let x = x == 1;\n    log(x);\n  }\n  \
+                   log(x);\n}";
+        assert_eq!(Vm::run_main(&wat(src)), "true\n1\n");
+    }
+}
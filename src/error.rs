@@ -0,0 +1,324 @@
+use crate::explain;
+use crate::lexer::{LexError, Pos};
+use crate::parser::ParseError;
+
+use std::{io, path::Path};
+
+// error of the whole compilation : parsing or semantic checks
+#[derive(Debug, Clone)]
+pub enum CompileError {
+    Parse(ParseError),
+    UnknownFunction {
+        name: String,
+        pos: Pos,
+    },
+    CallMain {
+        pos: Pos,
+    },
+    DuplicateFunction {
+        name: String,
+        first: Box<Pos>, // the first definition, boxed to keep the error small
+        second: Pos,
+    },
+    Unsupported {
+        what: String, // not handled by codegen yet
+    },
+    DivisionByZero {
+        pos: Pos, // the divisor
+    },
+    UnknownVariable {
+        name: String,
+        pos: Pos,
+    },
+    ArgCount {
+        name: String,
+        expected: usize, // number of parameters
+        found: usize,    // number of arguments
+        call: Pos,
+        def: Box<Pos>, // boxed to keep the error small
+    },
+    MixedReturn {
+        name: String,
+        pos: Pos, // the `return` that does not match the first one
+    },
+    MainReturnValue {
+        pos: Pos,
+    },
+    Io {
+        path: String, // file that could not be read or written
+        message: String,
+    },
+    ImportCycle {
+        chain: Vec<String>, // files of the cycle, the first one is repeated at the end
+    },
+    Several(Vec<CompileError>), // --fail-fast=false : every error found, at least two
+}
+
+impl From<ParseError> for CompileError {
+    fn from(e: ParseError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+impl From<LexError> for CompileError {
+    fn from(e: LexError) -> Self {
+        Self::Parse(ParseError::Lex(e))
+    }
+}
+
+impl CompileError {
+    pub fn io(path: &Path, e: io::Error) -> Self {
+        Self::Io {
+            path: path.to_string_lossy().into_owned(),
+            message: e.to_string(),
+        }
+    }
+
+    // diagnostic code, see explain.rs
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Parse(e) => e.code(),
+            Self::UnknownFunction { .. } => explain::E_UNKNOWN_FUNCTION,
+            Self::CallMain { .. } => explain::E_CALL_MAIN,
+            Self::DuplicateFunction { .. } => explain::E_DUPLICATE_FUNCTION,
+            Self::Unsupported { .. } => explain::E_UNSUPPORTED,
+            Self::DivisionByZero { .. } => explain::E_DIVISION_BY_ZERO,
+            Self::UnknownVariable { .. } => explain::E_UNKNOWN_VARIABLE,
+            Self::ArgCount { .. } => explain::E_ARG_COUNT,
+            Self::MixedReturn { .. } => explain::E_MIXED_RETURN,
+            Self::MainReturnValue { .. } => explain::E_MAIN_RETURN_VALUE,
+            Self::Io { .. } => explain::E_IO,
+            Self::ImportCycle { .. } => explain::E_IMPORT_CYCLE,
+            Self::Several(errors) => errors[0].code(),
+        }
+    }
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "{}", e),
+            Self::UnknownFunction { name, pos } => write!(
+                f,
+                "{}:{}:{}: error[{}]: call to unknown function `{}`",
+                pos.file,
+                pos.line,
+                pos.col,
+                self.code(),
+                name
+            ),
+            Self::CallMain { pos } => write!(
+                f,
+                "{}:{}:{}: error[{}]: `main` cannot be called",
+                pos.file,
+                pos.line,
+                pos.col,
+                self.code()
+            ),
+            Self::DuplicateFunction {
+                name,
+                first,
+                second,
+            } => write!(
+                f,
+                "{}:{}:{}: error[{}]: function `{}` already defined at {}:{}:{}",
+                second.file,
+                second.line,
+                second.col,
+                self.code(),
+                name,
+                first.file,
+                first.line,
+                first.col
+            ),
+            Self::Unsupported { what } => {
+                write!(f, "error[{}]: not supported yet: {}", self.code(), what)
+            }
+            Self::DivisionByZero { pos } => write!(
+                f,
+                "{}:{}:{}: error[{}]: division by zero",
+                pos.file,
+                pos.line,
+                pos.col,
+                self.code()
+            ),
+            Self::UnknownVariable { name, pos } => write!(
+                f,
+                "{}:{}:{}: error[{}]: unknown variable `{}`",
+                pos.file,
+                pos.line,
+                pos.col,
+                self.code(),
+                name
+            ),
+            Self::ArgCount {
+                name,
+                expected,
+                found,
+                call,
+                def,
+            } => write!(
+                f,
+                "{}:{}:{}: error[{}]: `{}` takes {} argument(s) but {} given (defined at {}:{}:{})",
+                call.file,
+                call.line,
+                call.col,
+                self.code(),
+                name,
+                expected,
+                found,
+                def.file,
+                def.line,
+                def.col
+            ),
+            Self::MixedReturn { name, pos } => write!(
+                f,
+                "{}:{}:{}: error[{}]: `{}` mixes `return` with and without a value",
+                pos.file,
+                pos.line,
+                pos.col,
+                self.code(),
+                name
+            ),
+            Self::MainReturnValue { pos } => write!(
+                f,
+                "{}:{}:{}: error[{}]: `main` cannot return a value",
+                pos.file,
+                pos.line,
+                pos.col,
+                self.code()
+            ),
+            Self::Io { path, message } => {
+                write!(f, "{}: error[{}]: {}", path, self.code(), message)
+            }
+            Self::ImportCycle { chain } => write!(
+                f,
+                "{}: error[{}]: circular import: {}",
+                chain[0],
+                self.code(),
+                chain.join(" -> ")
+            ),
+            // one diagnostic per line
+            Self::Several(errors) => {
+                let lines: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+        }
+    }
+}
+impl std::error::Error for CompileError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Lexer, Span, Token};
+    use std::collections::HashSet;
+
+    fn pos() -> Pos {
+        Pos {
+            byte: 0,
+            line: 1,
+            col: 1,
+            file: "t.gfr".into(),
+        }
+    }
+
+    fn lex_error(src: &str) -> CompileError {
+        let mut lx = Lexer::new(src);
+        loop {
+            match lx.next_token() {
+                Ok((Token::Eof, _)) => panic!("no error in {:?}", src),
+                Ok(_) => {}
+                Err(e) => return e.into(),
+            }
+        }
+    }
+
+    // every kind of error has its own code, explained by --explain
+    #[test]
+    fn each_error_has_a_unique_explained_code() {
+        let errors = [
+            ParseError::Unexpected {
+                found: Token::Eof,
+                expected: "x",
+                span: Box::new(Span {
+                    start: pos(),
+                    end: pos(),
+                }),
+            }
+            .into(),
+            ParseError::IntOverflow {
+                literal: "3000000000".into(),
+                span: Box::new(Span {
+                    start: pos(),
+                    end: pos(),
+                }),
+            }
+            .into(),
+            ParseError::FloatOverflow {
+                literal: format!("{}.0", "9".repeat(400)),
+                span: Box::new(Span {
+                    start: pos(),
+                    end: pos(),
+                }),
+            }
+            .into(),
+            lex_error("\"a"),
+            lex_error("#"),
+            ParseError::EmptyProgram { pos: pos() }.into(),
+            ParseError::MissingMain { pos: pos() }.into(),
+            CompileError::UnknownFunction {
+                name: "f".into(),
+                pos: pos(),
+            },
+            CompileError::CallMain { pos: pos() },
+            CompileError::DuplicateFunction {
+                name: "f".into(),
+                first: Box::new(pos()),
+                second: pos(),
+            },
+            CompileError::Unsupported { what: "x".into() },
+            CompileError::DivisionByZero { pos: pos() },
+            lex_error("/* a"),
+            lex_error("\"\\q\""),
+            lex_error("1.2.3"),
+            CompileError::UnknownVariable {
+                name: "x".into(),
+                pos: pos(),
+            },
+            ParseError::DuplicateParam {
+                name: "a".into(),
+                pos: pos(),
+            }
+            .into(),
+            CompileError::ArgCount {
+                name: "f".into(),
+                expected: 1,
+                found: 0,
+                call: pos(),
+                def: Box::new(pos()),
+            },
+            CompileError::MixedReturn {
+                name: "f".into(),
+                pos: pos(),
+            },
+            CompileError::MainReturnValue { pos: pos() },
+            ParseError::ChainedComparison { pos: pos() }.into(),
+            ParseError::NestingTooDeep { pos: pos() }.into(),
+            CompileError::Io {
+                path: "a.gfr".into(),
+                message: "not found".into(),
+            },
+            CompileError::ImportCycle {
+                chain: vec!["a.gfr".into(), "a.gfr".into()],
+            },
+        ];
+        let mut seen = HashSet::new();
+        for e in &errors {
+            assert!(seen.insert(e.code()), "{} used twice", e.code());
+            assert!(explain::explain(e.code()).is_some(), "{} not explained", e.code());
+            assert!(e.to_string().contains(&format!("error[{}]", e.code())));
+        }
+        assert_eq!(seen.len(), explain::CODES.len());
+    }
+}
@@ -0,0 +1,421 @@
+// stable diagnostic codes, printed with every error and looked up by `gaufre --explain CODE`
+pub const E_UNEXPECTED_TOKEN: &str = "E0001";
+pub const E_INT_OVERFLOW: &str = "E0002";
+pub const E_UNTERMINATED_STRING: &str = "E0003";
+pub const E_UNEXPECTED_CHAR: &str = "E0004";
+pub const E_EMPTY_PROGRAM: &str = "E0005";
+pub const E_MISSING_MAIN: &str = "E0006";
+pub const E_UNKNOWN_FUNCTION: &str = "E0007";
+pub const E_CALL_MAIN: &str = "E0008";
+pub const E_DUPLICATE_FUNCTION: &str = "E0009";
+pub const E_UNSUPPORTED: &str = "E0010";
+pub const E_DIVISION_BY_ZERO: &str = "E0011";
+pub const E_UNTERMINATED_COMMENT: &str = "E0012";
+pub const E_UNKNOWN_ESCAPE: &str = "E0013";
+pub const E_MALFORMED_NUMBER: &str = "E0014";
+pub const E_UNKNOWN_VARIABLE: &str = "E0015";
+pub const E_DUPLICATE_PARAM: &str = "E0016";
+pub const E_ARG_COUNT: &str = "E0017";
+pub const E_MIXED_RETURN: &str = "E0018";
+pub const E_MAIN_RETURN_VALUE: &str = "E0019";
+pub const E_CHAINED_COMPARISON: &str = "E0020";
+pub const E_IO: &str = "E0021";
+pub const E_IMPORT_CYCLE: &str = "E0022";
+pub const E_NESTING_TOO_DEEP: &str = "E0023";
+pub const E_FLOAT_OVERFLOW: &str = "E0024";
+
+// (code, long explanation with an example fix)
+pub const CODES: &[(&str, &str)] = &[
+    (
+        E_UNEXPECTED_TOKEN,
+        "The parser found a token that is not allowed at this place.
+
+Example:
+
+    fn main() {
+      log \"hello\"
+    }
+
+`log` must be followed by `(`. Fix:
+
+    fn main() {
+      log(\"hello\");
+    }",
+    ),
+    (
+        E_INT_OVERFLOW,
+        "An integer literal does not fit in a 32 bits signed integer (i32).
+
+Example:
+
+    log(3000000000);
+
+The largest allowed value is 2147483647. Use a smaller literal:
+
+    log(2147483647);",
+    ),
+    (
+        E_UNTERMINATED_STRING,
+        "A string literal was opened with `\"` but never closed before the end of the file.
+
+Example:
+
+    log(\"hello)
+
+Fix: close the string:
+
+    log(\"hello\");",
+    ),
+    (
+        E_UNEXPECTED_CHAR,
+        "The lexer found a character that does not start any token of the language.
+
+Example:
+
+    log(\"a\"); @
+
+Fix: remove the character, or put it inside a string:
+
+    log(\"a @\");",
+    ),
+    (
+        E_EMPTY_PROGRAM,
+        "The main file is empty. A program needs at least a `fn main` function.
+
+Fix:
+
+    fn main() {
+      log(\"hello\");
+    }",
+    ),
+    (
+        E_MISSING_MAIN,
+        "The main file only contains imports. After the imports it must define `fn main`,
+the entry point of the program.
+
+Example:
+
+    import \"lib/utils.gfr\"
+
+Fix:
+
+    import \"lib/utils.gfr\"
+
+    fn main() {
+      log(\"hello\");
+    }",
+    ),
+    (
+        E_UNKNOWN_FUNCTION,
+        "A `call` refers to a function that is not defined in any imported file.
+
+Example:
+
+    fn main() {
+      call greet();
+    }
+
+Fix: define the function in an imported file, or check its spelling:
+
+    import \"lib/greet.gfr\"
+
+    fn main() {
+      call greet();
+    }",
+    ),
+    (
+        E_CALL_MAIN,
+        "`main` is the entry point of the program and cannot be called explicitly.
+
+Example:
+
+    fn again() {
+      call main();
+    }
+
+Fix: move the code to call into its own function and call that function instead.",
+    ),
+    (
+        E_DUPLICATE_FUNCTION,
+        "Two functions have the same name, in the same file or in two imported files.
+Every function name must be unique in the whole program.
+
+Example:
+
+    // lib/a.gfr
+    fn hello() { log(\"a\"); }
+    // lib/b.gfr
+    fn hello() { log(\"b\"); }
+
+Fix: rename one of them:
+
+    // lib/b.gfr
+    fn hello_b() { log(\"b\"); }",
+    ),
+    (
+        E_UNSUPPORTED,
+        "The program is valid but uses a feature the code generator does not handle yet.
+
+Example:
+
+    let name = \"gaufre\";
+    log(name);
+
+Variables only hold integers for now. Fix: use the string directly:
+
+    log(\"gaufre\");",
+    ),
+    (
+        E_DIVISION_BY_ZERO,
+        "An integer division has a divisor equal to zero, known at compile time.
+The program would trap at runtime.
+
+Example:
+
+    log(10 / (2 - 2));
+
+Fix: divide by a value that cannot be zero:
+
+    log(10 / 2);",
+    ),
+    (
+        E_UNTERMINATED_COMMENT,
+        "A block comment was opened with `/*` but never closed before the end of the file.
+Block comments can be nested, each `/*` needs its own `*/`.
+
+Example:
+
+    /* outer /* inner */
+    fn main() {}
+
+Fix: close every comment:
+
+    /* outer /* inner */ */
+    fn main() {}",
+    ),
+    (
+        E_UNKNOWN_ESCAPE,
+        "A backslash in a string starts an escape sequence that does not exist.
+The escape sequences are \\n (newline), \\t (tab), \\r (carriage return),
+\\\\ (backslash) and \\\" (double quote).
+
+Example:
+
+    import \"lib\\utils.gfr\"
+
+Fix: use a slash in paths, or double the backslash:
+
+    import \"lib/utils.gfr\"",
+    ),
+    (
+        E_MALFORMED_NUMBER,
+        "A number literal is not well formed.
+
+Example:
+
+    log(1.2.3);
+
+A float literal has a single decimal point, 0x must be followed by
+hexadecimal digits and a `_` separator must be between two digits. Fix:
+
+    log(1.2);
+    log(0x1F);
+    log(1_000);",
+    ),
+    (
+        E_UNKNOWN_VARIABLE,
+        "A variable is used or assigned but no `let` declares it before, in the same
+function. A variable is visible from its `let` to the end of the function.
+
+Example:
+
+    fn main() {
+      log(x);
+      let x = 1;
+    }
+
+Fix: declare the variable before using it:
+
+    fn main() {
+      let x = 1;
+      log(x);
+    }",
+    ),
+    (
+        E_DUPLICATE_PARAM,
+        "Two parameters of the same function have the same name.
+
+Example:
+
+    fn add(a, a) {
+      log(a);
+    }
+
+Fix: give every parameter its own name:
+
+    fn add(a, b) {
+      log(a + b);
+    }",
+    ),
+    (
+        E_ARG_COUNT,
+        "A `call` does not give as many arguments as the function has parameters.
+
+Example:
+
+    fn add(a, b) {
+      log(a + b);
+    }
+
+    fn main() {
+      call add(1);
+    }
+
+Fix: give one argument per parameter:
+
+    fn main() {
+      call add(1, 2);
+    }",
+    ),
+    (
+        E_MIXED_RETURN,
+        "A function returns a value with `return x;` and also returns nothing with
+`return;`. A function either always returns an i32 value or never does.
+
+Example:
+
+    fn check(n) {
+      return;
+      return n;
+    }
+
+Fix: give a value to every `return`:
+
+    fn check(n) {
+      return 0;
+      return n;
+    }",
+    ),
+    (
+        E_MAIN_RETURN_VALUE,
+        "`main` is the entry point of the program and returns nothing, so its
+`return` cannot have a value.
+
+Example:
+
+    fn main() {
+      return 1;
+    }
+
+Fix: use `return;` without a value:
+
+    fn main() {
+      return;
+    }",
+    ),
+    (
+        E_CHAINED_COMPARISON,
+        "Two comparisons follow each other without parentheses. `a < b < c` does not
+check that b is between a and c, so it is rejected.
+
+Example:
+
+    if 0 < n < 10 {
+      log(n);
+    }
+
+Fix: nest two `if`, or add parentheses when comparing the result (0 or 1)
+of the first comparison is really wanted:
+
+    if 0 < n {
+      if n < 10 {
+        log(n);
+      }
+    }",
+    ),
+    (
+        E_IO,
+        "A file could not be read or written: the main file, an imported file or
+the output file. The message after the file name gives the reason.
+
+Example:
+
+    import \"lib/utils.gfr\"
+
+when lib/utils.gfr does not exist next to the importing file, or when an
+import pattern like \"lib/*.gfr\" matches no file.
+
+Fix: check the path. It is relative to the directory of the importing file,
+then to each directory given with -I.",
+    ),
+    (
+        E_IMPORT_CYCLE,
+        "A file imports itself, directly or through other imported files. The error
+lists the files of the cycle in import order.
+
+Example:
+
+    // a.gfr
+    import \"b.gfr\"
+    // b.gfr
+    import \"a.gfr\"
+
+Fix: move the functions both files need into a third file imported by both:
+
+    // a.gfr
+    import \"common.gfr\"
+    // b.gfr
+    import \"common.gfr\"",
+    ),
+    (
+        E_NESTING_TOO_DEEP,
+        "Parentheses, blocks or operators are nested more than 128 levels deep: a
+chain of 200 `+` is 200 levels. Such inputs are usually generated, the limit
+keeps the compiler from running out of stack.
+
+Example:
+
+    log(((((((...(1)...)))))));
+
+Fix: remove the useless parentheses, or compute the value in several steps:
+
+    let sum = a + b + c;
+    sum = sum + d + e;",
+    ),
+    (
+        E_FLOAT_OVERFLOW,
+        "A float literal is too large for a 64 bits float (f64): it would be
+infinite.
+
+Example:
+
+    log(1000...000.0); // 400 digits
+
+The largest allowed value is about 1.8 * 10^308. Use a smaller literal:
+
+    log(1000000.0);",
+    ),
+];
+
+// long explanation of a diagnostic code
+pub fn explain(code: &str) -> Option<&'static str> {
+    CODES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, text)| *text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_numbered_in_order() {
+        for (i, (code, text)) in CODES.iter().enumerate() {
+            assert_eq!(*code, format!("E{:04}", i + 1));
+            assert!(!text.is_empty());
+            assert_eq!(explain(code), Some(*text));
+        }
+        assert_eq!(explain("E9999"), None);
+    }
+}
@@ -3,11 +3,34 @@ pub const KW_FN:     &str = "fn";
 pub const KW_MAIN:   &str = "main";
 pub const KW_LOG:    &str = "log";
 pub const KW_CALL:   &str = "call"; 
+pub const KW_IF:     &str = "if";
+pub const KW_ELSE:   &str = "else";
+pub const KW_WHILE:  &str = "while";
+pub const KW_LET:    &str = "let";
+pub const KW_RETURN: &str = "return";
+pub const KW_TRUE:   &str = "true";
+pub const KW_FALSE:  &str = "false";
 
 pub const LPAREN:  &str = "(";
 pub const RPAREN:  &str = ")";
 pub const LBRACE:  &str = "{";
 pub const RBRACE:  &str = "}";
 pub const COMMA:   &str = ",";
+pub const PLUS:    &str = "+";
+pub const MINUS:   &str = "-";
+pub const STAR:    &str = "*";
+pub const SLASH:   &str = "/";
+pub const ASSIGN:  &str = "=";
+pub const EQ:      &str = "==";
+pub const NE:      &str = "!=";
+pub const LT:      &str = "<";
+pub const LE:      &str = "<=";
+pub const GT:      &str = ">";
+pub const GE:      &str = ">=";
+pub const SEMICOLON: &str = ";";
+
+pub const LINE_COMMENT: &str = "//";
+pub const BLOCK_COMMENT_START: &str = "/*";
+pub const BLOCK_COMMENT_END:   &str = "*/";
 
 pub const EOF:   &str = "end of file";
\ No newline at end of file
@@ -1,3 +1,4 @@
+use crate::explain;
 use crate::grammar;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -7,14 +8,35 @@ pub enum Token {
     Main,
     Log,
     Call,
+    If,
+    Else,
+    While,
+    Let,
+    Return,
+    True,
+    False,
     Ident(String),
     Number(String),
+    Float(String),
     Str(String),
     LParen,
     RParen,
     LBrace,
     RBrace,
     Comma,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Assign,
+    Semicolon,
+    Error, // placeholder for an invalid input in batch mode
     Eof,
 }
 
@@ -26,18 +48,58 @@ pub struct Pos {
     pub file: String, // source file name
 }
 
+// source text of a token : from start to end (excluded)
+#[derive(Debug, Clone)]
+pub struct Span {
+    pub start: Pos,
+    pub end: Pos,
+}
+
 #[derive(Debug, Clone)]
 pub struct LexError {
+    pub code: &'static str, // diagnostic code, see explain.rs
     pub message: String,
-    pub pos: Pos,
+    pub pos: Pos,         // start of the error
+    pub end_byte: usize,  // end of the error (excluded)
+}
+
+impl LexError {
+    // source byte range of the error, start..end (for editors)
+    pub fn byte_range(&self) -> std::ops::Range<usize> {
+        self.pos.byte..self.end_byte
+    }
+}
+
+// convert a byte offset into a (line, col) position, both starting at 1
+// and col counting characters, like the ones computed by the lexer
+pub fn line_col(src: &str, byte: usize) -> (usize, usize) {
+    let before = &src.as_bytes()[..byte.min(src.len())];
+    let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+    let line_start = before.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    let col = before[line_start..].iter().filter(|&&b| b & 0xC0 != 0x80).count() + 1;
+    (line, col)
+}
+
+// every token of src, the final Eof included, or the first error
+pub fn tokenize(src: &str) -> Result<Vec<(Token, Pos)>, LexError> {
+    let mut lx = Lexer::new(src);
+    let mut tokens = Vec::new();
+    loop {
+        let (t, pos) = lx.next_token()?;
+        let eof = t == Token::Eof;
+        tokens.push((t, pos));
+        if eof {
+            return Ok(tokens);
+        }
+    }
 }
 
 impl std::fmt::Display for LexError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{}:{}:{}: {}",
-            self.pos.file, self.pos.line, self.pos.col, self.message
+            "{}:{}:{}: error[{}]: {}",
+            self.pos.file, self.pos.line, self.pos.col, self.code, self.message
         )
     }
 }
@@ -48,6 +110,7 @@ pub struct Lexer<'a> {
     line: usize,    // line source code
     col: usize,     // column source code
     file: String,   // source file name
+    done: bool,     // the iterator has reached the end or an error
 }
 
 impl<'a> Lexer<'a> {
@@ -62,9 +125,15 @@ impl<'a> Lexer<'a> {
             line: 1,
             col: 1,
             file: file.into(),
+            done: false,
         }
     }
 
+    // byte offset of the cursor, i.e. the end of the last token read
+    pub fn offset(&self) -> usize {
+        self.i
+    }
+
     // check en of file
     fn eof(&self) -> bool {
         self.i >= self.input.len()
@@ -82,22 +151,64 @@ impl<'a> Lexer<'a> {
         if b == b'\n' {
             self.line += 1;
             self.col = 1;
-        } else {
+        } else if b & 0xC0 != 0x80 {
+            // columns count characters : utf-8 continuation bytes are skipped
             self.col += 1;
         }
         Some(b)
     }
 
-    // skip spaces and other separators
-    fn skip_ws(&mut self) {
+    // skip spaces, other separators and comments
+    fn skip_trivia(&mut self) -> Result<(), LexError> {
         while let Some(b) = self.peek() {
             match b {
                 b' ' | b'\t' | b'\r' | b'\n' => {
                     self.bump();
                 }
+                b'/' if self.starts_with(grammar::LINE_COMMENT) => self.skip_line_comment(),
+                b'/' if self.starts_with(grammar::BLOCK_COMMENT_START) => self.skip_block_comment()?,
                 _ => break,
             }
         }
+        Ok(())
+    }
+
+    // skip a /* ... */ comment, comments can be nested : /* a /* b */ c */
+    fn skip_block_comment(&mut self) -> Result<(), LexError> {
+        let start = self.get_pos();
+        let mut depth = 0;
+        while !self.eof() {
+            if self.starts_with(grammar::BLOCK_COMMENT_START) {
+                self.bump();
+                self.bump();
+                depth += 1;
+            } else if self.starts_with(grammar::BLOCK_COMMENT_END) {
+                self.bump();
+                self.bump();
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+            } else {
+                self.bump(); // keeps line/col up to date on newlines
+            }
+        }
+        Err(LexError {
+            code: explain::E_UNTERMINATED_COMMENT,
+            message: "unterminated block comment (*/ missing)".into(),
+            end_byte: start.byte + grammar::BLOCK_COMMENT_START.len(),
+            pos: start,
+        })
+    }
+
+    // skip from `//` to the end of the line, the newline is left to skip_trivia
+    fn skip_line_comment(&mut self) {
+        while let Some(b) = self.peek() {
+            if b == b'\n' {
+                break;
+            }
+            self.bump();
+        }
     }
 
     // check if the input starts with the searched token
@@ -108,8 +219,10 @@ impl<'a> Lexer<'a> {
     // checks if the next token is the one being searched for (s)
     fn try_take(&mut self, s: &str) -> bool {
         if self.starts_with(s) {
-            self.i += s.len();
-            self.col += s.len();
+            // through bump so that line and col follow any symbol text
+            for _ in 0..s.len() {
+                self.bump();
+            }
             true
         } else {
             false
@@ -133,6 +246,43 @@ impl<'a> Lexer<'a> {
         if self.try_take(grammar::COMMA) {
             return Some(Token::Comma);
         }
+        if self.try_take(grammar::PLUS) {
+            return Some(Token::Plus);
+        }
+        if self.try_take(grammar::MINUS) {
+            return Some(Token::Minus);
+        }
+        if self.try_take(grammar::STAR) {
+            return Some(Token::Star);
+        }
+        if self.try_take(grammar::SLASH) {
+            return Some(Token::Slash);
+        }
+        // two characters symbols first : `==` is not two `=`
+        if self.try_take(grammar::EQ) {
+            return Some(Token::Eq);
+        }
+        if self.try_take(grammar::NE) {
+            return Some(Token::Ne);
+        }
+        if self.try_take(grammar::LE) {
+            return Some(Token::Le);
+        }
+        if self.try_take(grammar::GE) {
+            return Some(Token::Ge);
+        }
+        if self.try_take(grammar::LT) {
+            return Some(Token::Lt);
+        }
+        if self.try_take(grammar::GT) {
+            return Some(Token::Gt);
+        }
+        if self.try_take(grammar::ASSIGN) {
+            return Some(Token::Assign);
+        }
+        if self.try_take(grammar::SEMICOLON) {
+            return Some(Token::Semicolon);
+        }
         None
     }
 
@@ -145,35 +295,86 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    // read a valid string
+    // read a valid string, escape sequences \n \t \r \\ \" are replaced by their byte
     fn read_string(&mut self) -> Result<Token, LexError> {
-        let start_byte = self.i;
-        let start_line = self.line;
-        let start_col = self.col;
+        let start = self.get_pos(); // an unterminated string is reported here
         self.bump(); // "
-        let s = self.i;
+        let mut out = String::new();
+        let mut s = self.i; // start of the text not copied yet
         while let Some(b) = self.peek() {
-            if b == b'"' {
-                let out = &self.input[s..self.i];
-                self.bump();
-                return Ok(Token::Str(out.to_string()));
+            match b {
+                b'"' => {
+                    out.push_str(&self.input[s..self.i]);
+                    self.bump();
+                    return Ok(Token::Str(out));
+                }
+                b'\\' => {
+                    out.push_str(&self.input[s..self.i]);
+                    let esc_pos = self.get_pos();
+                    self.bump(); // backslash
+                    let c = match self.peek() {
+                        Some(b'n') => '\n',
+                        Some(b't') => '\t',
+                        Some(b'r') => '\r',
+                        Some(b'\\') => '\\',
+                        Some(b'"') => '"',
+                        None => break, // unterminated string
+                        Some(_) => {
+                            let len = self.input[self.i..].chars().next().map_or(1, char::len_utf8);
+                            let e = LexError {
+                                code: explain::E_UNKNOWN_ESCAPE,
+                                message: format!(
+                                    "unknown escape sequence \\{}",
+                                    &self.input[self.i..self.i + len]
+                                ),
+                                end_byte: self.i + len,
+                                pos: esc_pos,
+                            };
+                            // the rest of the string is not code : lexing can
+                            // go on after the closing quote
+                            self.skip_string_end();
+                            return Err(e);
+                        }
+                    };
+                    self.bump();
+                    out.push(c);
+                    s = self.i;
+                }
+                _ => {
+                    self.bump();
+                }
             }
-            self.bump();
         }
         Err(LexError {
+            code: explain::E_UNTERMINATED_STRING,
             message: "incomplete string (\" missing)".into(),
-            pos: self.get_pos(),
+            pos: start,
+            end_byte: self.i,
         })
     }
 
+    // move past the closing quote of the string the cursor is in, escaped
+    // quotes included
+    fn skip_string_end(&mut self) {
+        while let Some(b) = self.bump() {
+            match b {
+                b'"' => return,
+                b'\\' => {
+                    self.bump(); // the escaped character
+                }
+                _ => {}
+            }
+        }
+    }
+
     // ident can start with a upper or lower case letter or underscore
     fn is_ident_start(b: u8) -> bool {
-        (b'a'..=b'z').contains(&b) || (b'A'..=b'Z').contains(&b) || b == b'_'
+        b.is_ascii_alphabetic() || b == b'_'
     }
 
     // check the next characters of the ident same as ident_start plus digits
     fn is_ident_continue(b: u8) -> bool {
-        Self::is_ident_start(b) || (b'0'..=b'9').contains(&b)
+        Self::is_ident_start(b) || b.is_ascii_digit()
     }
 
     fn read_ident(&mut self) -> (&'a str, usize, usize) {
@@ -188,21 +389,95 @@ impl<'a> Lexer<'a> {
         (&self.input[s..self.i], s, self.i) // return the ident, start and end position
     }
 
-    fn read_number(&mut self) -> (&'a str, usize, usize) {
-        let s = self.i;
+    // read digits, `_` is allowed between two digits as a separator : 1_000_000
+    // the separators are removed from the returned text
+    fn read_digits(&mut self, is_digit: fn(&u8) -> bool) -> Result<String, LexError> {
+        let mut out = String::new();
         while let Some(b) = self.peek() {
-            if (b'0'..=b'9').contains(&b) {
+            if is_digit(&b) {
+                out.push(b as char);
+                self.bump();
+            } else if b == b'_' {
+                let next = self.input.as_bytes().get(self.i + 1);
+                if out.is_empty() || !next.is_some_and(is_digit) {
+                    // 5_ 1__0 0x_1
+                    return Err(LexError {
+                        code: explain::E_MALFORMED_NUMBER,
+                        message: "malformed number: `_` must be between two digits".into(),
+                        pos: self.get_pos(),
+                        end_byte: self.i + 1,
+                    });
+                }
                 self.bump();
             } else {
                 break;
             }
         }
-        (&self.input[s..self.i], s, self.i) // return the number, start and end position
+        Ok(out)
+    }
+
+    // true if the cursor is on a `.` followed by a digit
+    fn at_fraction(&self) -> bool {
+        let bytes = self.input.as_bytes();
+        bytes.get(self.i) == Some(&b'.') && bytes.get(self.i + 1).is_some_and(u8::is_ascii_digit)
+    }
+
+    // 0x1F : the token holds the decimal value so that the parser reads it like any integer
+    fn read_hex_number(&mut self) -> Result<Token, LexError> {
+        self.bump(); // 0
+        self.bump(); // x
+        let digits = self.read_digits(u8::is_ascii_hexdigit)?;
+        if digits.is_empty() {
+            return Err(LexError {
+                code: explain::E_MALFORMED_NUMBER,
+                message: "malformed number: no digit after 0x".into(),
+                pos: self.get_pos(),
+                end_byte: self.i,
+            });
+        }
+        // too large values keep their text, the parser reports them as IntOverflow
+        Ok(Token::Number(match u64::from_str_radix(&digits, 16) {
+            Ok(v) => v.to_string(),
+            Err(_) => format!("0x{}", digits),
+        }))
+    }
+
+    // integer 42, hexadecimal 0x2A or float 3.14
+    fn read_number(&mut self) -> Result<Token, LexError> {
+        if self.starts_with("0x") || self.starts_with("0X") {
+            return self.read_hex_number();
+        }
+        let int_part = self.read_digits(u8::is_ascii_digit)?;
+        if !self.at_fraction() {
+            return Ok(Token::Number(int_part));
+        }
+        self.bump(); // .
+        let frac_part = self.read_digits(u8::is_ascii_digit)?;
+        if self.at_fraction() {
+            // 1.2.3
+            return Err(LexError {
+                code: explain::E_MALFORMED_NUMBER,
+                message: "malformed number: second decimal point".into(),
+                pos: self.get_pos(),
+                end_byte: self.i + 1,
+            });
+        }
+        Ok(Token::Float(format!("{}.{}", int_part, frac_part)))
     }
 
     // get next valid token
     pub fn next_token(&mut self) -> Result<(Token, Pos), LexError> {
-        self.skip_ws();
+        self.next_token_span().map(|(t, span)| (t, span.start))
+    }
+
+    // next token with its whole span
+    pub fn next_token_span(&mut self) -> Result<(Token, Span), LexError> {
+        let (t, start) = self.read_token()?;
+        Ok((t, Span { start, end: self.get_pos() }))
+    }
+
+    fn read_token(&mut self) -> Result<(Token, Pos), LexError> {
+        self.skip_trivia()?;
         let pos = Pos {
             file: self.file.clone(),
             byte: self.i,
@@ -230,21 +505,326 @@ impl<'a> Lexer<'a> {
                         grammar::KW_FN => Token::Fn,
                         grammar::KW_MAIN => Token::Main,
                         grammar::KW_LOG => Token::Log,
+                        grammar::KW_IF => Token::If,
+                        grammar::KW_ELSE => Token::Else,
+                        grammar::KW_WHILE => Token::While,
+                        grammar::KW_LET => Token::Let,
+                        grammar::KW_RETURN => Token::Return,
+                        grammar::KW_TRUE => Token::True,
+                        grammar::KW_FALSE => Token::False,
                         _ => Token::Ident(id.to_string()), // if not it is an ident
                     },
                     pos,
                 ));
             }
             // check if the token is a number
-            if (b'0'..=b'9').contains(&b) {
-                let (n, _, _) = self.read_number();
-                return Ok((Token::Number(n.to_string()), pos));
+            if b.is_ascii_digit() {
+                return Ok((self.read_number()?, pos));
             }
         }
 
         Err(LexError {
-            message: format!("caractère inattendu: 0x{:02X}", self.peek().unwrap()),
+            code: explain::E_UNEXPECTED_CHAR,
+            message: format!("unexpected character: 0x{:02X}", self.peek().unwrap()),
             pos: self.get_pos(),
+            end_byte: self.i + self.input[self.i..].chars().next().map_or(1, char::len_utf8),
         })
     }
+
+    // source text of a token
+    pub fn source(&self, span: &Span) -> &'a str {
+        &self.input[span.start.byte..span.end.byte]
+    }
+
+    // move past the input of an error so that lexing can go on
+    pub fn skip_error(&mut self, e: &LexError) {
+        if self.i == e.pos.byte {
+            self.skip_char(); // still on the bad character : skip it
+        }
+    }
+
+    // skip the character (not only the byte) under the cursor
+    fn skip_char(&mut self) {
+        self.bump();
+        while let Some(b) = self.peek() {
+            if b & 0xC0 != 0x80 {
+                break; // not an utf-8 continuation byte
+            }
+            self.bump();
+        }
+    }
+
+    // lex the whole input without stopping at the first error: every invalid
+    // input is recorded and replaced by a Token::Error in the token stream
+    pub fn tokenize_batch(&mut self) -> (Vec<(Token, Pos)>, Vec<LexError>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            match self.next_token() {
+                Ok((Token::Eof, pos)) => {
+                    tokens.push((Token::Eof, pos));
+                    break;
+                }
+                Ok(t) => tokens.push(t),
+                Err(e) => {
+                    self.skip_error(&e);
+                    tokens.push((Token::Error, e.pos.clone()));
+                    errors.push(e);
+                }
+            }
+        }
+        (tokens, errors)
+    }
+}
+
+// the tokens before Eof, or the first error then nothing more
+impl Iterator for Lexer<'_> {
+    type Item = Result<(Token, Pos), LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_token() {
+            Ok((Token::Eof, _)) => {
+                self.done = true;
+                None
+            }
+            Ok(t) => Some(Ok(t)),
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_reports_every_stray_character() {
+        let (tokens, errors) = Lexer::new("log(# 1 @ $)").tokenize_batch();
+        let cols: Vec<usize> = errors.iter().map(|e| e.pos.col).collect();
+        assert_eq!(cols, [5, 9, 11]);
+        let tokens: Vec<Token> = tokens.into_iter().map(|(t, _)| t).collect();
+        assert_eq!(
+            tokens,
+            [
+                Token::Log,
+                Token::LParen,
+                Token::Error,
+                Token::Number("1".into()),
+                Token::Error,
+                Token::Error,
+                Token::RParen,
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_error_byte_ranges() {
+        let (_, errors) = Lexer::new("é log(\"ab").tokenize_batch();
+        let ranges: Vec<_> = errors.iter().map(|e| e.byte_range()).collect();
+        // the multibyte character, then the string from its opening quote
+        assert_eq!(ranges, [0..2, 7..10]);
+    }
+
+    #[test]
+    fn line_col_of_a_byte_offset() {
+        let src = "ab\ncd\n";
+        assert_eq!(line_col(src, 0), (1, 1));
+        assert_eq!(line_col(src, 2), (1, 3));
+        assert_eq!(line_col(src, 4), (2, 2));
+        assert_eq!(line_col(src, 99), (3, 1)); // past the end: the end of the file
+    }
+
+    #[test]
+    fn long_identifier_is_one_token() {
+        // one token, whatever the length
+        let name = "x".repeat(1_000_000);
+        let mut lx = Lexer::new(&name);
+        assert_eq!(lx.next_token().unwrap().0, Token::Ident(name.clone()));
+        let (eof, pos) = lx.next_token().unwrap();
+        assert_eq!((eof, pos.col), (Token::Eof, 1_000_001));
+    }
+
+    #[test]
+    fn line_comments_are_skipped() {
+        let mut lx = Lexer::new("// a // b\n6 / 2 // end");
+        let (t, pos) = lx.next_token().unwrap();
+        assert_eq!((t, pos.line, pos.col), (Token::Number("6".into()), 2, 1));
+        assert_eq!(lx.next_token().unwrap().0, Token::Slash);
+        assert_eq!(lx.next_token().unwrap().0, Token::Number("2".into()));
+        assert_eq!(lx.next_token().unwrap().0, Token::Eof);
+    }
+
+    #[test]
+    fn nested_block_comment() {
+        let (t, pos) = Lexer::new("/* a /* b */\n c */ log").next_token().unwrap();
+        assert_eq!((t, pos.line, pos.col), (Token::Log, 2, 7));
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        let mut lx = Lexer::new("log /* a /* b */\n");
+        lx.next_token().unwrap();
+        let e = lx.next_token().unwrap_err();
+        assert_eq!(e.code, explain::E_UNTERMINATED_COMMENT);
+        assert_eq!((e.pos.line, e.pos.col), (1, 5)); // the opening `/*`
+        assert_eq!(e.byte_range(), 4..6);
+    }
+
+    #[test]
+    fn string_escapes() {
+        let mut lx = Lexer::new(r#""a\nb\t\r\\\"" x"#);
+        assert_eq!(lx.next_token().unwrap().0, Token::Str("a\nb\t\r\\\"".into()));
+        assert_eq!(lx.next_token().unwrap().1.col, 16); // raw columns, escapes count two
+    }
+
+    #[test]
+    fn unknown_escape() {
+        let mut lx = Lexer::new(r#"log("a\q \" b") x"#);
+        lx.next_token().unwrap();
+        lx.next_token().unwrap();
+        let e = lx.next_token().unwrap_err();
+        assert_eq!(e.code, explain::E_UNKNOWN_ESCAPE);
+        assert_eq!(e.message, "unknown escape sequence \\q");
+        assert_eq!(e.pos.col, 7); // the backslash
+        assert_eq!(e.byte_range(), 6..8);
+        // lexing goes on after the closing quote, the escaped one is skipped
+        let (t, pos) = lx.next_token().unwrap();
+        assert_eq!((t, pos.col), (Token::RParen, 15));
+        assert_eq!(lx.next_token().unwrap().0, Token::Ident("x".into()));
+    }
+
+    #[test]
+    fn column_after_symbols() {
+        let mut lx = Lexer::new("log((1+2),x)");
+        let cols: Vec<usize> = std::iter::from_fn(|| match lx.next_token().unwrap() {
+            (Token::Eof, _) => None,
+            (_, pos) => Some(pos.col),
+        })
+        .collect();
+        assert_eq!(cols, [1, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+    }
+
+    #[test]
+    fn unexpected_character_message() {
+        let e = Lexer::new("#").next_token().unwrap_err();
+        assert_eq!(e.message, "unexpected character: 0x23");
+        // the first byte of a multi-byte character, the range covers all of it
+        let e = Lexer::new("é").next_token().unwrap_err();
+        assert_eq!(e.message, "unexpected character: 0xC3");
+        assert_eq!(e.byte_range(), 0..2);
+    }
+
+    #[test]
+    fn float_literals() {
+        let mut lx = Lexer::new("3.14 2");
+        assert_eq!(lx.next_token().unwrap().0, Token::Float("3.14".into()));
+        assert_eq!(lx.next_token().unwrap().0, Token::Number("2".into()));
+    }
+
+    #[test]
+    fn second_decimal_point() {
+        let mut lx = Lexer::new("log(1.2.3)");
+        lx.next_token().unwrap();
+        lx.next_token().unwrap();
+        let e = lx.next_token().unwrap_err();
+        assert_eq!(e.code, explain::E_MALFORMED_NUMBER);
+        assert_eq!((e.pos.col, e.byte_range()), (8, 7..8)); // the second point
+    }
+
+    #[test]
+    fn hexadecimal_literals() {
+        let mut lx = Lexer::new("0x1F 0Xff 0x0");
+        for n in ["31", "255", "0"] {
+            assert_eq!(lx.next_token().unwrap().0, Token::Number(n.into()));
+        }
+        let e = Lexer::new("0x;").next_token().unwrap_err();
+        assert_eq!((e.code, e.pos.col), (explain::E_MALFORMED_NUMBER, 3));
+    }
+
+    #[test]
+    fn digit_separators() {
+        let mut lx = Lexer::new("1_000_000 0xFF_FF 1_0.2_5");
+        assert_eq!(lx.next_token().unwrap().0, Token::Number("1000000".into()));
+        assert_eq!(lx.next_token().unwrap().0, Token::Number("65535".into()));
+        assert_eq!(lx.next_token().unwrap().0, Token::Float("10.25".into()));
+        for (src, col) in [("5_", 2), ("1__0", 2), ("0x_1", 3)] {
+            let e = Lexer::new(src).next_token().unwrap_err();
+            assert_eq!((e.code, e.pos.col), (explain::E_MALFORMED_NUMBER, col), "{}", src);
+        }
+    }
+
+    #[test]
+    fn comparison_operators() {
+        let mut lx = Lexer::new("== = != <= < >= >");
+        let tokens: Vec<Token> = std::iter::from_fn(|| match lx.next_token().unwrap() {
+            (Token::Eof, _) => None,
+            (t, _) => Some(t),
+        })
+        .collect();
+        let want = [Token::Eq, Token::Assign, Token::Ne, Token::Le];
+        assert_eq!(tokens[..4], want);
+        assert_eq!(tokens[4..], [Token::Lt, Token::Ge, Token::Gt]);
+    }
+
+    #[test]
+    fn token_spans() {
+        let mut lx = Lexer::new("log(\"a\nb\") // end");
+        let spans: Vec<((usize, usize), (usize, usize))> = std::iter::from_fn(|| {
+            match lx.next_token_span().unwrap() {
+                (Token::Eof, _) => None,
+                (_, s) => Some(((s.start.line, s.start.col), (s.end.line, s.end.col))),
+            }
+        })
+        .collect();
+        // the string goes over two lines, the comment is not part of `)`
+        assert_eq!(spans, [((1, 1), (1, 4)), ((1, 4), (1, 5)), ((1, 5), (2, 3)), ((2, 3), (2, 4))]);
+    }
+
+    #[test]
+    fn iterator_stops_at_eof_or_after_the_first_error() {
+        let tokens: Vec<Token> = Lexer::new("log(1)").map(|r| r.unwrap().0).collect();
+        assert_eq!(
+            tokens,
+            [Token::Log, Token::LParen, Token::Number("1".into()), Token::RParen]
+        );
+        let mut lx = Lexer::new("1 # 2");
+        assert!(lx.next().unwrap().is_ok());
+        assert!(lx.next().unwrap().is_err());
+        assert!(lx.next().is_none());
+    }
+
+    #[test]
+    fn tokenize_returns_every_token_or_the_first_error() {
+        let tokens = tokenize("log(1)").unwrap();
+        let kinds: Vec<_> = tokens.iter().map(|(t, _)| t.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                Token::Log,
+                Token::LParen,
+                Token::Number("1".into()),
+                Token::RParen,
+                Token::Eof
+            ]
+        );
+        assert_eq!(tokens[4].1.col, 7);
+        let e = tokenize("log(\"ab").unwrap_err();
+        assert_eq!((e.pos.line, e.pos.col), (1, 5));
+    }
+
+    #[test]
+    fn columns_count_characters_after_multibyte_text() {
+        let src = "log(\"é\") x";
+        let mut lx = Lexer::new(src);
+        let pos = (0..5).map(|_| lx.next_token().unwrap().1).last().unwrap();
+        assert_eq!((pos.col, pos.byte), (10, 10));
+        assert_eq!(line_col(src, 10), (1, 10));
+    }
 }
@@ -0,0 +1,121 @@
+pub mod bundle;
+pub mod codegen;
+pub mod error;
+pub mod explain;
+pub mod grammar;
+pub mod lexer;
+pub mod loader;
+pub mod parser;
+pub mod resolve;
+pub mod wasm;
+#[cfg(test)]
+mod wat_vm;
+
+use error::CompileError;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+// compilation settings given on the command line
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub import_root: Option<PathBuf>,      // base directory of the imports starting with `/`
+    pub defines: HashMap<String, String>,  // symbols for `import "x.gfr" if NAME`
+    pub search_paths: Vec<PathBuf>,        // -I directories, tried after the importing file one
+    pub debug_comments: bool,              // `;; file:line:col` before the WAT of each statement
+    pub fail_fast: bool,                   // stop at the first semantic error
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            import_root: None,
+            defines: HashMap::new(),
+            search_paths: Vec::new(),
+            debug_comments: true,
+            fail_fast: true,
+        }
+    }
+}
+
+// Compile the program of the root file and its imports into WAT text
+pub fn compile(root: &Path) -> Result<String, CompileError> {
+    compile_with(root, &Options::default())
+}
+
+pub fn compile_with(root: &Path, options: &Options) -> Result<String, CompileError> {
+    let (root_prog, fns) = loader::load_program(root, options)?;
+
+    // check calls against the defined functions, variables against their `let`
+    // and the kind of every `return`
+    if options.fail_fast {
+        resolve::resolve_calls(&root_prog, &fns)?;
+        resolve::resolve_vars(&root_prog, &fns)?;
+        resolve::resolve_returns(&root_prog, &fns)?;
+    } else {
+        let mut errors = resolve::resolve_all(&root_prog, &fns);
+        match errors.len() {
+            0 => {}
+            1 => return Err(errors.remove(0)),
+            _ => return Err(CompileError::Several(errors)),
+        }
+    }
+
+    codegen::emit_module(&root_prog, &fns, options)
+}
+
+// Compile the program into a binary wasm module, assembled from the WAT text
+pub fn compile_wasm(root: &Path, options: &Options) -> Result<Vec<u8>, CompileError> {
+    wasm::assemble(&compile_with(root, options)?)
+}
+
+// One self contained source file for the program of the root file and its imports
+pub fn bundle(root: &Path, options: &Options) -> Result<String, CompileError> {
+    let (root_prog, fns) = loader::load_program(root, options)?;
+    Ok(bundle::bundle_source(&root_prog, &fns))
+}
+
+// The parsed program and the functions of its imports, pretty printed with
+// their Debug impls, to debug the parser
+pub fn dump_ast(root: &Path, options: &Options) -> Result<String, CompileError> {
+    let (root_prog, fns) = loader::load_program(root, options)?;
+    let mut out = format!("{:#?}\n", root_prog);
+    for f in &fns {
+        out.push_str(&format!("{:#?}\n", f));
+    }
+    Ok(out)
+}
+
+// Parse a single expression, for tools that evaluate one outside of a
+// program. Tokens left after the expression are an error.
+pub fn parse_expression(src: &str) -> Result<parser::Expr, parser::ParseError> {
+    parser::Parser::new(lexer::Lexer::new(src))?.parse_whole_expr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lexer::Token;
+    use parser::{Expr, ParseError, Value};
+
+    #[test]
+    fn parse_expression_alone() {
+        let e = parse_expression("1 + 2 + x").unwrap();
+        assert!(matches!(e, Expr::Add(..)));
+        assert_eq!(parse_expression(" 1 + 2 ").unwrap().const_eval(), Some(Value::Int(3)));
+    }
+
+    #[test]
+    fn parse_expression_rejects_trailing_tokens() {
+        let e = parse_expression("1 + 2 3").unwrap_err();
+        match &e {
+            ParseError::Unexpected { found, expected, .. } => {
+                assert_eq!(*found, Token::Number("3".into()));
+                assert_eq!(*expected, grammar::EOF);
+            }
+            _ => panic!("unexpected error {:?}", e),
+        }
+        assert_eq!(e.byte_range(), 6..7);
+    }
+}
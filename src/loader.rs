@@ -0,0 +1,317 @@
+use crate::Options;
+use crate::error::CompileError;
+use crate::lexer::{Lexer, Pos};
+use crate::parser::{Function, Parser, Program};
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+// An import starting with `/` is resolved from the --import-root directory when
+// one is given (otherwise it is a plain absolute path), any other import is
+// resolved from the directory of the importing file.
+fn resolve_rel(base_file: &Path, rel: &str, import_root: Option<&Path>) -> PathBuf {
+    if let (Some(root), Some(stripped)) = (import_root, rel.strip_prefix('/')) {
+        return root.join(stripped);
+    }
+    let base_dir = base_file.parent().unwrap_or_else(|| Path::new("."));
+    base_dir.join(rel)
+}
+
+// Find the file of an import without wildcard : first resolve_rel, then for
+// a relative import each -I search directory in order
+fn find_import(base_file: &Path, rel: &str, options: &Options) -> io::Result<PathBuf> {
+    let mut tried = vec![resolve_rel(base_file, rel, options.import_root.as_deref())];
+    if !rel.starts_with('/') {
+        tried.extend(options.search_paths.iter().map(|dir| dir.join(rel)));
+    }
+    if let Some(found) = tried.iter().find(|p| p.is_file()) {
+        return Ok(found.clone());
+    }
+    let tried: Vec<String> = tried.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("import \"{}\" not found, tried: {}", rel, tried.join(", ")),
+    ))
+}
+
+fn is_glob(s: &str) -> bool {
+    s.contains('*') || s.contains('?')
+}
+
+// match a file name against a pattern : `*` any sequence, `?` any character
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            glob_match(&pattern[1..], name) || (!name.is_empty() && glob_match(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => glob_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => glob_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+// every sub directory of dir, dir included (for `**`)
+fn walk_dirs(dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    out.push(dir.to_path_buf());
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk_dirs(&path, out)?;
+        }
+    }
+    Ok(())
+}
+
+// Expand an import path into the files to load, sorted for a stable output.
+// `*` and `?` match inside one directory level, only `**` goes down the sub directories.
+// An import without wildcard is looked up with find_import.
+fn expand_import(base_file: &Path, rel: &str, options: &Options) -> io::Result<Vec<PathBuf>> {
+    if !is_glob(rel) {
+        return Ok(vec![find_import(base_file, rel, options)?]);
+    }
+    let full = resolve_rel(base_file, rel, options.import_root.as_deref());
+    let mut candidates = vec![PathBuf::new()];
+    for comp in full.components() {
+        let part = match comp {
+            Component::Normal(p) => p.to_string_lossy().into_owned(),
+            other => {
+                // root, prefix, `.` or `..` : taken as is
+                candidates = candidates.iter().map(|c| c.join(other)).collect();
+                continue;
+            }
+        };
+        let mut next = Vec::new();
+        if part == "**" {
+            for c in candidates.iter() {
+                let dir = if c.as_os_str().is_empty() { Path::new(".") } else { c };
+                if dir.is_dir() {
+                    walk_dirs(dir, &mut next)?;
+                }
+            }
+        } else if is_glob(&part) {
+            let pattern: Vec<char> = part.chars().collect();
+            for c in candidates.iter() {
+                // an empty path is the current directory
+                let dir = if c.as_os_str().is_empty() { Path::new(".") } else { c };
+                if !dir.is_dir() {
+                    continue;
+                }
+                for entry in fs::read_dir(dir)? {
+                    let path = entry?.path();
+                    let name: Vec<char> = path
+                        .file_name()
+                        .map(|n| n.to_string_lossy().chars().collect())
+                        .unwrap_or_default();
+                    if glob_match(&pattern, &name) {
+                        next.push(path);
+                    }
+                }
+            }
+        } else {
+            next = candidates.iter().map(|c| c.join(&part)).collect();
+        }
+        candidates = next;
+    }
+    let mut files: Vec<PathBuf> = candidates.into_iter().filter(|p| p.is_file()).collect();
+    files.sort();
+    files.dedup();
+    if files.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no file matches import \"{}\"", rel),
+        ));
+    }
+    Ok(files)
+}
+
+// Parse the root file and load its imports : returns the body of `main` and
+// every function, the ones of the root file first
+pub fn load_program(
+    root_path: &Path,
+    options: &Options,
+) -> Result<(Program, Vec<Function>), CompileError> {
+    // 1) main program parsing : imports + fn main { ... }
+    let src_root = read_source(root_path)?;
+    let lx_root = Lexer::with_file(root_path.to_string_lossy(), &src_root);
+    let mut p = Parser::new(lx_root)?;
+    let (imports, root_prog, mut fns) = p.parse_main_program()?; // Program { stmts }
+    let root_file = root_path.to_string_lossy().into_owned();
+    let mut defined_at: HashMap<String, Pos> = HashMap::new(); // function name -> definition
+    for f in &fns {
+        check_duplicate(&mut defined_at, f)?;
+    }
+
+    // 2) Load every import, then the imports of the imported files : each
+    // file is loaded once, files are identified by their canonical path
+    let root_key = fs::canonicalize(root_path).map_err(|e| CompileError::io(root_path, e))?;
+    let mut names = HashMap::from([(root_key.clone(), root_file)]); // file name to print
+    let mut graph: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new(); // file -> its imports
+    let mut queue = VecDeque::from([(root_path.to_path_buf(), root_key.clone(), imports)]);
+    while let Some((from, from_key, imports)) = queue.pop_front() {
+        let mut edges = Vec::new();
+        for import in imports {
+            // import "x.gfr" if NAME : skipped when NAME is not defined
+            if let Some(cond) = &import.cond
+                && !options.defines.contains_key(cond)
+            {
+                continue;
+            }
+            // build import full paths from rel path (several ones for a glob),
+            // relative to the importing file
+            let files = expand_import(&from, &import.path, options)
+                .map_err(|e| CompileError::io(&from, e))?;
+            for full in files {
+                let key = fs::canonicalize(&full).map_err(|e| CompileError::io(&full, e))?;
+                edges.push(key.clone());
+                if names.contains_key(&key) {
+                    continue; // remove import duplicates
+                }
+                let src = read_source(&full)?;
+                let file = full.to_string_lossy().into_owned();
+                let lx = Lexer::with_file(file.clone(), &src); // new lexer for the import
+                let mut p = Parser::new(lx)?;
+                let (sub_imports, part) = p.parse_sub_functions()?; // parse import
+                for f in &part {
+                    check_duplicate(&mut defined_at, f)?;
+                }
+                fns.extend(part);
+                names.insert(key.clone(), file);
+                queue.push_back((full, key, sub_imports));
+            }
+        }
+        graph.insert(from_key, edges);
+    }
+
+    // 3) a file cannot import itself, even through other files
+    if let Some(cycle) = find_cycle(&graph, &root_key, &mut Vec::new(), &mut HashSet::new()) {
+        let chain = cycle.iter().map(|k| names[*k].clone()).collect();
+        return Err(CompileError::ImportCycle { chain });
+    }
+    Ok((root_prog, fns))
+}
+
+// Depth first search of the import graph from `file`. `stack` holds the files
+// whose imports are being visited : meeting one of them again closes a cycle,
+// returned with its first file repeated at the end.
+fn find_cycle<'a>(
+    graph: &'a HashMap<PathBuf, Vec<PathBuf>>,
+    file: &'a PathBuf,
+    stack: &mut Vec<&'a PathBuf>,
+    done: &mut HashSet<&'a PathBuf>,
+) -> Option<Vec<&'a PathBuf>> {
+    if let Some(i) = stack.iter().position(|f| *f == file) {
+        let mut cycle = stack[i..].to_vec();
+        cycle.push(file);
+        return Some(cycle);
+    }
+    if !done.insert(file) {
+        return None; // already visited from another importer
+    }
+    stack.push(file);
+    for import in graph.get(file).into_iter().flatten() {
+        if let Some(cycle) = find_cycle(graph, import, stack, done) {
+            return Some(cycle);
+        }
+    }
+    stack.pop();
+    None
+}
+
+// content of a source file, the error names the file
+fn read_source(path: &Path) -> Result<String, CompileError> {
+    fs::read_to_string(path).map_err(|e| CompileError::io(path, e))
+}
+
+// a function name can be defined only once in the whole program
+fn check_duplicate(
+    defined_at: &mut HashMap<String, Pos>,
+    f: &Function,
+) -> Result<(), CompileError> {
+    if let Some(first) = defined_at.get(&f.name) {
+        return Err(CompileError::DuplicateFunction {
+            name: f.name.clone(),
+            first: Box::new(first.clone()),
+            second: f.pos.clone(),
+        });
+    }
+    defined_at.insert(f.name.clone(), f.pos.clone());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(rel: &str) -> Vec<PathBuf> {
+        expand_import(Path::new("exemple/hello.gfr"), rel, &Options::default()).unwrap()
+    }
+
+    #[test]
+    fn glob_import_lists_matching_files_sorted() {
+        let lib = Path::new("exemple/lib");
+        assert_eq!(expand("lib/*.gfr"), [lib.join("unit.gfr"), lib.join("utils.gfr")]);
+        assert_eq!(expand("lib/?n*.gfr"), [lib.join("unit.gfr")]);
+    }
+
+    #[test]
+    fn glob_matching_nothing_is_an_error() {
+        let base = Path::new("exemple/hello.gfr");
+        let e = expand_import(base, "lib/*.txt", &Options::default()).unwrap_err();
+        assert_eq!(e.to_string(), "no file matches import \"lib/*.txt\"");
+    }
+
+    #[test]
+    fn double_star_goes_down_sub_directories() {
+        let files = expand("**/*.gfr");
+        let dir = Path::new("exemple");
+        assert_eq!(
+            files,
+            [
+                dir.join("hello.gfr"),
+                dir.join("lib").join("unit.gfr"),
+                dir.join("lib").join("utils.gfr"),
+            ]
+        );
+    }
+
+    #[test]
+    fn import_without_wildcard_is_searched_in_the_directories() {
+        assert_eq!(expand("lib/unit.gfr"), [Path::new("exemple/lib/unit.gfr")]);
+        let options = Options {
+            search_paths: vec!["src".into(), "exemple/lib".into()],
+            ..Options::default()
+        };
+        let files = expand_import(Path::new("a/main.gfr"), "unit.gfr", &options).unwrap();
+        assert_eq!(files, [Path::new("exemple/lib/unit.gfr")]);
+        // every tried path is listed when none exists
+        let e = expand_import(Path::new("a/main.gfr"), "none.gfr", &options).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "import \"none.gfr\" not found, tried: a/none.gfr, src/none.gfr, exemple/lib/none.gfr"
+        );
+    }
+
+    #[test]
+    fn slash_import_starts_at_the_import_root() {
+        let base = Path::new("exemple/lib/unit.gfr");
+        let options = Options {
+            import_root: Some("exemple".into()),
+            // not used for an import starting with `/`
+            search_paths: vec!["exemple/lib".into()],
+            ..Options::default()
+        };
+        let files = expand_import(base, "/lib/utils.gfr", &options).unwrap();
+        assert_eq!(files, [Path::new("exemple/lib/utils.gfr")]);
+        let files = expand_import(base, "/*.gfr", &options).unwrap();
+        assert_eq!(files, [Path::new("exemple/hello.gfr")]);
+        let e = expand_import(base, "/utils.gfr", &options).unwrap_err();
+        assert!(e.to_string().ends_with("tried: exemple/utils.gfr"), "{}", e);
+        // without a root it is an absolute path
+        let e = expand_import(base, "/lib/utils.gfr", &Options::default()).unwrap_err();
+        assert!(e.to_string().ends_with("tried: /lib/utils.gfr"), "{}", e);
+    }
+}
@@ -1,54 +1,84 @@
-mod lexer;
-mod parser;
-mod grammar;
+mod cli;
 
-use lexer::Lexer;
-use parser::Parser;
-use std::{
-    collections::HashSet,
-    env, fs,
-    path::{Path, PathBuf},
-};
+use cli::{Cli, Command, Emit};
+use gaufre::error::CompileError;
+use gaufre::explain;
+use gaufre::lexer::{Lexer, Token};
+use std::{env, fs, path::Path, process::ExitCode};
 
-fn resolve_rel(base_file: &Path, rel: &str) -> PathBuf {
-    let base_dir = base_file.parent().unwrap_or_else(|| Path::new("."));
-    base_dir.join(rel)
+// why gaufre stopped
+enum Failure {
+    Usage(String),         // bad command line, printed with the usage : exit code 2
+    Compile(CompileError), // exit code 1
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let root_path = PathBuf::from(
-        env::args()
-            .nth(1)
-            .expect("usage: gaufre <root.gfr> [out.wat]"),
-    );
-    let out_path = env::args().nth(2);
-
-    // 1) main program parsing : imports + fn main { ... }
-    let src_root = fs::read_to_string(&root_path)?;
-    let lx_root = Lexer::with_file(root_path.to_string_lossy(), &src_root);
-    let mut p = Parser::new(lx_root)?;
-    let (imports, root_prog) = p.parse_main_program()?; // Program { stmts }
+impl From<CompileError> for Failure {
+    fn from(e: CompileError) -> Self {
+        Self::Compile(e)
+    }
+}
 
-    // 2) Load every import (no import in these files)
-    let mut imported_stmts = Vec::new();
-    let mut seen = HashSet::new(); 
-    for rel in imports {
-        let full = resolve_rel(&root_path, &rel); // build import full path from rel path
-        if !seen.insert(full.clone()) { // remove import duplicates 
-            continue;
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Usage(message)) => {
+            eprintln!("error: {}\n\n{}", message, cli::USAGE);
+            ExitCode::from(2)
+        }
+        Err(Failure::Compile(e)) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
         }
-        let src = fs::read_to_string(&full)?;
-        let lx = Lexer::with_file(full.to_string_lossy(), &src); // new lexer for the import
-        let mut p = Parser::new(lx)?;
-        let mut part = p.parse_sub_programs()?; // parse import 
-        imported_stmts.append(&mut part);
     }
+}
+
+// write an output file, the error names it
+fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), CompileError> {
+    fs::write(path, contents).map_err(|e| CompileError::io(path, e))
+}
 
-    // 3) WAT code generation
-    let wat = "test";
+fn run() -> Result<(), Failure> {
+    let cli = Cli::parse(env::args().skip(1)).map_err(Failure::Usage)?;
 
-    let default_out = root_path.with_extension("wat");
-    let out = out_path.unwrap_or_else(|| default_out.to_string_lossy().into_owned());
-    fs::write(&out, wat)?;
+    match cli.command {
+        Command::Help => println!("{}", cli::USAGE),
+        Command::Version => println!("gaufre {}", env!("CARGO_PKG_VERSION")),
+        // gaufre --explain E0001
+        Command::Explain(code) => {
+            let text = explain::explain(&code)
+                .ok_or_else(|| Failure::Usage(format!("unknown error code {}", code)))?;
+            println!("{}", text);
+        }
+        Command::Bundle { root, out } => {
+            // one self contained source file, printed on stdout without -o
+            let src = gaufre::bundle(&root, &cli.options)?;
+            match out {
+                Some(out) => write(&out, src)?,
+                None => print!("{}", src),
+            }
+        }
+        // gaufre --dump-ast root.gfr : the imports are loaded, no code is generated
+        Command::DumpAst { root } => print!("{}", gaufre::dump_ast(&root, &cli.options)?),
+        // gaufre --dump-tokens root.gfr : only the lexer runs, on the root file.
+        // The tokens before a lex error are printed too.
+        Command::DumpTokens { root } => {
+            let src = fs::read_to_string(&root).map_err(|e| CompileError::io(&root, e))?;
+            let mut lx = Lexer::with_file(root.to_string_lossy(), &src);
+            loop {
+                let (token, pos) = lx.next_token().map_err(CompileError::from)?;
+                println!("{}:{}:{} {:?}", pos.file, pos.line, pos.col, token);
+                if token == Token::Eof {
+                    break;
+                }
+            }
+        }
+        Command::Compile { root, out, emit } => {
+            let out = out.unwrap_or_else(|| root.with_extension(emit.extension()));
+            match emit {
+                Emit::Wat => write(&out, gaufre::compile_with(&root, &cli.options)?)?,
+                Emit::Wasm => write(&out, gaufre::compile_wasm(&root, &cli.options)?)?,
+            }
+        }
+    }
     Ok(())
 }
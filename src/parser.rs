@@ -1,31 +1,250 @@
+use crate::explain;
 use crate::grammar;
-use crate::lexer::{LexError, Lexer, Pos, Token};
+use crate::lexer::{LexError, Lexer, Pos, Span, Token};
 
-use std::fmt;
+use std::cmp::Ordering;
 
 #[derive(Debug, Clone)]
 pub struct Program {
     pub stmts: Vec<Stmt>,
 }
 
+// import "path" [if NAME]
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub path: String,
+    pub cond: Option<String>, // only loaded when this symbol is defined
+}
+
 #[derive(Debug, Clone)]
 pub struct Function {
     pub name: String,
+    pub params: Vec<String>,
     pub body: Vec<Stmt>,
+    pub pos: Pos, // position of the name
 }
 
 #[derive(Debug, Clone)]
 pub enum Stmt {
-    Log(Vec<Expr>),
-    Call { name: String },
+    Log { args: Vec<Expr>, pos: Pos },
+    Call { name: String, args: Vec<Expr>, pos: Pos },
+    Let { name: String, value: Expr, pos: Pos }, // let name = value;
+    Assign { name: String, value: Expr, pos: Pos }, // name = value;
+    Return { value: Option<Expr>, pos: Pos },        // return [value];
+    If {
+        cond: Expr, // true when not 0
+        then: Vec<Stmt>,
+        els: Option<Vec<Stmt>>,
+        pos: Pos,
+    },
+    While {
+        cond: Expr, // checked before each iteration
+        body: Vec<Stmt>,
+        pos: Pos,
+    },
+}
+
+impl Stmt {
+    // position of the first token of the statement
+    pub fn pos(&self) -> &Pos {
+        match self {
+            Stmt::Log { pos, .. }
+            | Stmt::Call { pos, .. }
+            | Stmt::Let { pos, .. }
+            | Stmt::Assign { pos, .. }
+            | Stmt::Return { pos, .. }
+            | Stmt::If { pos, .. }
+            | Stmt::While { pos, .. } => pos,
+        }
+    }
+
+    // arms of an `if` and of its `else if` chain, then the final `else`
+    // block : the chain is walked in a loop, it is not bounded by MAX_NESTING
+    pub fn if_arms(&self) -> (Vec<IfArm<'_>>, Option<&[Stmt]>) {
+        let mut arms = Vec::new();
+        let mut stmt = self;
+        while let Stmt::If { cond, then, els, pos } = stmt {
+            arms.push((pos, cond, then.as_slice()));
+            match els.as_deref() {
+                Some([next @ Stmt::If { .. }]) => stmt = next,
+                last => return (arms, last),
+            }
+        }
+        (arms, None)
+    }
+}
+
+// position, condition and block of one arm of an `if`
+pub type IfArm<'a> = (&'a Pos, &'a Expr, &'a [Stmt]);
+
+// call f on every statement, the ones nested in blocks included
+pub fn visit_stmts<'a>(stmts: &'a [Stmt], f: &mut impl FnMut(&'a Stmt)) {
+    for stmt in stmts {
+        f(stmt);
+        match stmt {
+            Stmt::If { then, els, .. } => {
+                visit_stmts(then, f);
+                if let Some(els) = els {
+                    visit_stmts(els, f);
+                }
+            }
+            Stmt::While { body, .. } => visit_stmts(body, f),
+            _ => {}
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Expr {
-    Str(String),
-    Var(String),
-    Int(i32),
+    Str { value: String, pos: Pos },
+    Var { name: String, pos: Pos },
+    Int { value: i32, pos: Pos },
+    Float { value: f64, pos: Pos },
+    Bool { value: bool, pos: Pos }, // an i32 1 or 0 at runtime
+    // operators, starting at their left operand
     Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    // comparisons, 1 when true and 0 when false
+    Eq(Box<Expr>, Box<Expr>),
+    Ne(Box<Expr>, Box<Expr>),
+    Lt(Box<Expr>, Box<Expr>),
+    Le(Box<Expr>, Box<Expr>),
+    Gt(Box<Expr>, Box<Expr>),
+    Ge(Box<Expr>, Box<Expr>),
+}
+
+impl Function {
+    // true if a `return` gives a value : the function has an i32 result
+    pub fn returns_value(&self) -> bool {
+        let mut found = false;
+        visit_stmts(&self.body, &mut |s| {
+            found |= matches!(s, Stmt::Return { value: Some(_), .. });
+        });
+        found
+    }
+}
+
+// value of an expression known at compile time
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+}
+
+// Text of a float the way the lexer reads it : digits, a point and a
+// fraction, never an exponent (1e20 is printed 100000000000000000000.0).
+// inf and NaN, only from constant folding, are printed as is.
+pub fn float_text(x: f64) -> String {
+    let text = x.to_string();
+    if text.contains('.') || !x.is_finite() {
+        text
+    } else {
+        text + ".0"
+    }
+}
+
+impl Expr {
+    // true for a boolean literal or a comparison, printed as true or false
+    pub fn is_bool(&self) -> bool {
+        matches!(
+            self,
+            Expr::Bool { .. }
+                | Expr::Eq(..)
+                | Expr::Ne(..)
+                | Expr::Lt(..)
+                | Expr::Le(..)
+                | Expr::Gt(..)
+                | Expr::Ge(..)
+        )
+    }
+
+    // position of the first token of the expression
+    pub fn pos(&self) -> &Pos {
+        match self {
+            Expr::Str { pos, .. }
+            | Expr::Var { pos, .. }
+            | Expr::Int { pos, .. }
+            | Expr::Float { pos, .. }
+            | Expr::Bool { pos, .. } => pos,
+            Expr::Add(a, _)
+            | Expr::Sub(a, _)
+            | Expr::Mul(a, _)
+            | Expr::Div(a, _)
+            | Expr::Eq(a, _)
+            | Expr::Ne(a, _)
+            | Expr::Lt(a, _)
+            | Expr::Le(a, _)
+            | Expr::Gt(a, _)
+            | Expr::Ge(a, _) => a.pos(),
+        }
+    }
+
+    // true if the expression can be computed at compile time
+    pub fn is_constant(&self) -> bool {
+        self.const_eval().is_some()
+    }
+
+    // compute the expression at compile time, None if it depends on a variable,
+    // overflows i32 or mixes types (booleans are not integers here)
+    pub fn const_eval(&self) -> Option<Value> {
+        match self {
+            Expr::Str { value, .. } => Some(Value::Str(value.clone())),
+            Expr::Int { value, .. } => Some(Value::Int(*value)),
+            Expr::Float { value, .. } => Some(Value::Float(*value)),
+            Expr::Bool { value, .. } => Some(Value::Bool(*value)),
+            Expr::Var { .. } => None,
+            Expr::Add(a, b) => match (a.const_eval()?, b.const_eval()?) {
+                (Value::Str(x), Value::Str(y)) => Some(Value::Str(x + &y)), // concatenation
+                (x, y) => Self::const_num_op(x, y, i32::checked_add, |x, y| x + y),
+            },
+            Expr::Sub(a, b) => {
+                Self::const_num_op(a.const_eval()?, b.const_eval()?, i32::checked_sub, |x, y| x - y)
+            }
+            Expr::Mul(a, b) => {
+                Self::const_num_op(a.const_eval()?, b.const_eval()?, i32::checked_mul, |x, y| x * y)
+            }
+            // None when dividing an integer by 0
+            Expr::Div(a, b) => {
+                Self::const_num_op(a.const_eval()?, b.const_eval()?, i32::checked_div, |x, y| x / y)
+            }
+            Expr::Eq(a, b) => Self::const_cmp(a.const_eval()?, b.const_eval()?, Ordering::is_eq),
+            Expr::Ne(a, b) => Self::const_cmp(a.const_eval()?, b.const_eval()?, Ordering::is_ne),
+            Expr::Lt(a, b) => Self::const_cmp(a.const_eval()?, b.const_eval()?, Ordering::is_lt),
+            Expr::Le(a, b) => Self::const_cmp(a.const_eval()?, b.const_eval()?, Ordering::is_le),
+            Expr::Gt(a, b) => Self::const_cmp(a.const_eval()?, b.const_eval()?, Ordering::is_gt),
+            Expr::Ge(a, b) => Self::const_cmp(a.const_eval()?, b.const_eval()?, Ordering::is_ge),
+        }
+    }
+
+    // comparison at compile time, both operands must have the same type
+    fn const_cmp(a: Value, b: Value, test: fn(Ordering) -> bool) -> Option<Value> {
+        let ord = match (a, b) {
+            (Value::Int(x), Value::Int(y)) => x.cmp(&y),
+            (Value::Float(x), Value::Float(y)) => x.partial_cmp(&y)?, // None for NaN
+            (Value::Str(x), Value::Str(y)) => x.cmp(&y),
+            (Value::Bool(x), Value::Bool(y)) => x.cmp(&y),
+            _ => return None,
+        };
+        Some(Value::Bool(test(ord)))
+    }
+
+    // arithmetic operation at compile time, both operands must have the same type
+    fn const_num_op(
+        a: Value,
+        b: Value,
+        int_op: fn(i32, i32) -> Option<i32>,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Option<Value> {
+        match (a, b) {
+            (Value::Int(x), Value::Int(y)) => int_op(x, y).map(Value::Int),
+            (Value::Float(x), Value::Float(y)) => Some(Value::Float(float_op(x, y))),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -34,12 +253,32 @@ pub enum ParseError {
     Unexpected {
         found: Token,
         expected: &'static str,
-        pos: Pos,
+        span: Box<Span>, // the found token, boxed to keep the error small
     },
     IntOverflow {
-        literal: String,
+        literal: String, // source text, with its `_` separators or 0x prefix
+        span: Box<Span>, // the whole literal, boxed to keep the error small
+    },
+    FloatOverflow {
+        literal: String, // source text, with its `_` separators
+        span: Box<Span>, // the whole literal, boxed to keep the error small
+    },
+    EmptyProgram {
+        pos: Pos,
+    },
+    MissingMain {
+        pos: Pos,
+    },
+    DuplicateParam {
+        name: String,
         pos: Pos,
     },
+    ChainedComparison {
+        pos: Pos, // second comparison operator
+    },
+    NestingTooDeep {
+        pos: Pos, // the `(`, `{` or operator one level too deep
+    },
 }
 
 impl From<LexError> for ParseError {
@@ -48,6 +287,38 @@ impl From<LexError> for ParseError {
     }
 }
 
+impl ParseError {
+    // source byte range of the error, start..end (for editors)
+    pub fn byte_range(&self) -> std::ops::Range<usize> {
+        match self {
+            Self::Lex(e) => e.byte_range(),
+            Self::Unexpected { span, .. } => span.start.byte..span.end.byte,
+            Self::IntOverflow { span, .. } | Self::FloatOverflow { span, .. } => {
+                span.start.byte..span.end.byte
+            }
+            Self::EmptyProgram { pos } | Self::MissingMain { pos } => pos.byte..pos.byte,
+            Self::DuplicateParam { name, pos } => pos.byte..pos.byte + name.len(),
+            Self::ChainedComparison { pos } => pos.byte..pos.byte,
+            Self::NestingTooDeep { pos } => pos.byte..pos.byte + 1,
+        }
+    }
+
+    // diagnostic code, see explain.rs
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Lex(e) => e.code,
+            Self::Unexpected { .. } => explain::E_UNEXPECTED_TOKEN,
+            Self::IntOverflow { .. } => explain::E_INT_OVERFLOW,
+            Self::FloatOverflow { .. } => explain::E_FLOAT_OVERFLOW,
+            Self::EmptyProgram { .. } => explain::E_EMPTY_PROGRAM,
+            Self::MissingMain { .. } => explain::E_MISSING_MAIN,
+            Self::DuplicateParam { .. } => explain::E_DUPLICATE_PARAM,
+            Self::ChainedComparison { .. } => explain::E_CHAINED_COMPARISON,
+            Self::NestingTooDeep { .. } => explain::E_NESTING_TOO_DEEP,
+        }
+    }
+}
+
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -55,40 +326,174 @@ impl std::fmt::Display for ParseError {
             Self::Unexpected {
                 found,
                 expected,
-                pos,
+                span,
             } => write!(
                 f,
-                "{}:{}:{}: Expected {}, found {:?}",
-                pos.file, pos.line, pos.col, expected, found
+                "{}:{}:{}: error[{}]: Expected {}, found {:?}",
+                span.start.file,
+                span.start.line,
+                span.start.col,
+                self.code(),
+                expected,
+                found
+            ),
+            Self::IntOverflow { literal, span } => write!(
+                f,
+                "{}:{}:{}: error[{}]: integer out of i32 range: {}",
+                span.start.file,
+                span.start.line,
+                span.start.col,
+                self.code(),
+                literal
             ),
-            Self::IntOverflow { literal, pos } => write!(
+            Self::FloatOverflow { literal, span } => write!(
                 f,
-                "{}:{}:{}: Entier hors plage i32: {}",
-                pos.file, pos.line, pos.col, literal
+                "{}:{}:{}: error[{}]: float out of f64 range: {}",
+                span.start.file,
+                span.start.line,
+                span.start.col,
+                self.code(),
+                literal
+            ),
+            Self::EmptyProgram { pos } => write!(
+                f,
+                "{}:{}:{}: error[{}]: empty program: expected `fn main`",
+                pos.file,
+                pos.line,
+                pos.col,
+                self.code()
+            ),
+            Self::MissingMain { pos } => write!(
+                f,
+                "{}:{}:{}: error[{}]: no `fn main` found",
+                pos.file,
+                pos.line,
+                pos.col,
+                self.code()
+            ),
+            Self::DuplicateParam { name, pos } => write!(
+                f,
+                "{}:{}:{}: error[{}]: parameter `{}` is declared twice",
+                pos.file,
+                pos.line,
+                pos.col,
+                self.code(),
+                name
+            ),
+            Self::ChainedComparison { pos } => write!(
+                f,
+                "{}:{}:{}: error[{}]: comparisons cannot be chained, use parentheses",
+                pos.file,
+                pos.line,
+                pos.col,
+                self.code()
+            ),
+            Self::NestingTooDeep { pos } => write!(
+                f,
+                "{}:{}:{}: error[{}]: nesting too deep, more than {} levels",
+                pos.file,
+                pos.line,
+                pos.col,
+                self.code(),
+                MAX_NESTING
             ),
         }
     }
 }
 impl std::error::Error for ParseError {}
 
+// binding power of the comparisons, below `+` and `-`
+const CMP_BP: u8 = 5;
+
+// deepest nesting of parentheses, blocks and operators
+pub const MAX_NESTING: usize = 128;
+
 pub struct Parser<'a> {
     lx: Lexer<'a>, // lexer
     cur: Token,    // current token
-    cur_pos: Pos,  // curent position
+    cur_span: Span, // position of the current token
+    prev_end: Pos,  // end of the previous token
+    recover: bool,  // errors are recorded in `errors` and parsing goes on
+    errors: Vec<ParseError>,
+    depth: usize, // nested parentheses and blocks
 }
 
 impl<'a> Parser<'a> {
     pub fn new(mut lx: Lexer<'a>) -> Result<Self, ParseError> {
-        let (cur, cur_pos) = lx.next_token()?;
-        Ok(Self { lx, cur, cur_pos })
+        let (cur, cur_span) = lx.next_token_span()?;
+        Ok(Self {
+            lx,
+            prev_end: cur_span.start.clone(),
+            cur,
+            cur_span,
+            recover: false,
+            errors: Vec::new(),
+            depth: 0,
+        })
+    }
+
+    // Parse one nesting level deeper with f. The parser is recursive : the
+    // depth is bounded so that a pathological input gives an error instead of
+    // overflowing the stack.
+    fn nested<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if self.depth >= MAX_NESTING {
+            return Err(ParseError::NestingTooDeep {
+                pos: self.cur_span.start.clone(),
+            });
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
     }
 
-    // Move one token forward
+    // Move one token forward. When recovering, an invalid input is recorded
+    // and skipped, so this never fails.
     fn bump(&mut self) -> Result<(), ParseError> {
-        (self.cur, self.cur_pos) = self.lx.next_token()?;
+        loop {
+            match self.lx.next_token_span() {
+                Ok(t) => {
+                    self.prev_end = self.cur_span.end.clone();
+                    (self.cur, self.cur_span) = t;
+                    break;
+                }
+                Err(e) if self.recover => {
+                    self.lx.skip_error(&e);
+                    self.errors.push(e.into());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
         Ok(())
     }
 
+    // skip tokens until one accepted by stop, or the end of file
+    fn skip_until(&mut self, stop: fn(&Token) -> bool) {
+        while !stop(&self.cur) && !matches!(self.cur, Token::Eof) {
+            if self.bump().is_err() {
+                break; // only when not recovering
+            }
+        }
+    }
+
+    // a token where a statement can start again after an error
+    fn is_stmt_boundary(t: &Token) -> bool {
+        matches!(
+            t,
+            Token::Log
+                | Token::Call
+                | Token::Let
+                | Token::Return
+                | Token::If
+                | Token::While
+                | Token::RBrace
+                | Token::Fn
+        )
+    }
+
     // Checks if the current token matches the expected value; otherwise, it returns an error
     fn expect(&mut self, want: Token, name: &'static str) -> Result<(), ParseError> {
         if std::mem::discriminant(&self.cur) == std::mem::discriminant(&want) {
@@ -98,126 +503,419 @@ impl<'a> Parser<'a> {
             Err(ParseError::Unexpected {
                 found: self.cur.clone(),
                 expected: name,
-                pos: self.cur_pos.clone(),
+                span: Box::new(self.cur_span.clone()),
             })
         }
     }
 
+    // `;` at the end of a statement, a missing one is reported just after
+    // the statement rather than on the next token
+    fn expect_semicolon(&mut self) -> Result<(), ParseError> {
+        if matches!(self.cur, Token::Semicolon) {
+            return self.bump();
+        }
+        Err(ParseError::Unexpected {
+            found: self.cur.clone(),
+            expected: grammar::SEMICOLON,
+            span: Box::new(Span {
+                start: self.prev_end.clone(),
+                end: self.prev_end.clone(),
+            }),
+        })
+    }
+
     // import "string"
     // fn main() {}
-    pub fn parse_main_program(&mut self) -> Result<(Vec<String>, Program), ParseError> {
+    // fn other() {} : other functions are allowed before or after main
+    pub fn parse_main_program(
+        &mut self,
+    ) -> Result<(Vec<Import>, Program, Vec<Function>), ParseError> {
+        if matches!(self.cur, Token::Eof) {
+            return Err(ParseError::EmptyProgram {
+                pos: self.cur_span.start.clone(),
+            });
+        }
         let imports = self.parse_imports()?;
-        // fn main() { ... }
+        let mut main = None;
+        let mut fns = Vec::new();
+        let stmts = loop {
+            if matches!(self.cur, Token::Eof) {
+                match main {
+                    Some(stmts) => break stmts,
+                    None => {
+                        return Err(ParseError::MissingMain {
+                            pos: self.cur_span.start.clone(),
+                        });
+                    }
+                }
+            }
+            self.parse_top_function(&mut main, &mut fns)?;
+        };
+        Ok((imports, Program { stmts }, fns))
+    }
+
+    // Like parse_main_program, but every error is recorded and parsing goes
+    // on from the next statement, or the next `fn` for an error outside a
+    // function body. Only `main` is returned.
+    pub fn parse_main_program_recover(&mut self) -> (Program, Vec<ParseError>) {
+        self.recover = true;
+        let mut main = None;
+        if matches!(self.cur, Token::Eof) {
+            self.errors.push(ParseError::EmptyProgram {
+                pos: self.cur_span.start.clone(),
+            });
+        }
+        if let Err(e) = self.parse_imports() {
+            self.errors.push(e);
+            self.skip_until(|t| matches!(t, Token::Fn));
+        }
+        let mut fns = Vec::new();
+        while !matches!(self.cur, Token::Eof) {
+            if let Err(e) = self.parse_top_function(&mut main, &mut fns) {
+                self.errors.push(e);
+                self.skip_until(|t| matches!(t, Token::Fn));
+            }
+        }
+        if main.is_none() && self.errors.is_empty() {
+            self.errors.push(ParseError::MissingMain {
+                pos: self.cur_span.start.clone(),
+            });
+        }
+        self.recover = false;
+        let stmts = main.unwrap_or_default();
+        (Program { stmts }, std::mem::take(&mut self.errors))
+    }
+
+    // fn main() { ... } the first time, or any other function
+    fn parse_top_function(
+        &mut self,
+        main: &mut Option<Vec<Stmt>>,
+        fns: &mut Vec<Function>,
+    ) -> Result<(), ParseError> {
         self.expect(Token::Fn, grammar::KW_FN)?;
-        self.expect(Token::Main, grammar::KW_MAIN)?;
-        self.expect(Token::LParen, grammar::LPAREN)?;
-        self.expect(Token::RParen, grammar::RPAREN)?;
-        self.expect(Token::LBrace, grammar::LBRACE)?;
-        let mut stmts = Vec::new();
-        while !matches!(self.cur, Token::RBrace) {
-            stmts.push(self.parse_stmt()?);
+        if matches!(self.cur, Token::Main) && main.is_none() {
+            self.bump()?; // 'main'
+            *main = Some(self.parse_fn_body_block()?);
+        } else {
+            fns.push(self.parse_function()?);
         }
-        self.expect(Token::RBrace, grammar::RBRACE)?;
-        self.expect(Token::Eof, grammar::EOF)?;
-        Ok((imports, Program { stmts }))
+        Ok(())
     }
 
     /// Read import and return the path to the import, zero import is allowed
-    pub fn parse_imports(&mut self) -> Result<Vec<String>, ParseError> {
-        let mut paths = Vec::new();
-        loop {
-            match self.cur {
-                Token::Import => {
-                    self.bump()?; // 'import'
-                    if let Token::Str(s) = &self.cur {
-                        paths.push(s.clone());
-                        self.bump()?; // string
-                    } else {
-                        return Err(ParseError::Unexpected {
-                            found: self.cur.clone(),
-                            expected: "a path string after `import`",
-                            pos: self.cur_pos.clone(),
-                        });
-                    }
+    pub fn parse_imports(&mut self) -> Result<Vec<Import>, ParseError> {
+        let mut imports = Vec::new();
+        while let Token::Import = self.cur {
+            self.bump()?; // 'import'
+            let path = if let Token::Str(s) = &self.cur {
+                let p = s.clone();
+                self.bump()?; // string
+                p
+            } else {
+                return Err(ParseError::Unexpected {
+                    found: self.cur.clone(),
+                    expected: "a path string after `import`",
+                    span: Box::new(self.cur_span.clone()),
+                });
+            };
+            // optional condition : if NAME
+            let cond = if matches!(self.cur, Token::If) {
+                self.bump()?; // 'if'
+                if let Token::Ident(s) = &self.cur {
+                    let name = s.clone();
+                    self.bump()?;
+                    Some(name)
+                } else {
+                    return Err(ParseError::Unexpected {
+                        found: self.cur.clone(),
+                        expected: "a symbol name after `if`",
+                        span: Box::new(self.cur_span.clone()),
+                    });
                 }
-                _ => break,
-            }
+            } else {
+                None
+            };
+            imports.push(Import { path, cond });
         }
-        Ok(paths)
+        Ok(imports)
     }
 
-    // parse the log primitive : log(" string ")
+    // parse the log primitive : log(expr, expr, ...);
     fn parse_log(&mut self) -> Result<Stmt, ParseError> {
+        let pos = self.cur_span.start.clone();
         self.expect(Token::Log, grammar::KW_LOG)?;
+        let args = self.parse_args()?;
+        self.expect_semicolon()?;
+        Ok(Stmt::Log { args, pos })
+    }
+
+    // parse the arguments of log or call : (expr, expr, ...)
+    fn parse_args(&mut self) -> Result<Vec<Expr>, ParseError> {
         self.expect(Token::LParen, grammar::LPAREN)?;
-        // Get the string
-        let s = if let Token::Str(txt) = &self.cur {
-            let out = txt.clone();
-            self.bump()?; // eat the string
-            out
-        } else {
-            return Err(ParseError::Unexpected {
-                found: self.cur.clone(),
-                expected: "a string \"...\" after log(",
-                pos: self.cur_pos.clone(),
-            });
-        };
+        let mut args = Vec::new();
+        while !matches!(self.cur, Token::RParen) {
+            args.push(self.parse_expr()?);
+            if !matches!(self.cur, Token::Comma) {
+                break;
+            }
+            // a comma must be followed by another argument
+            let comma_span = self.cur_span.clone();
+            self.bump()?; // ','
+            if matches!(self.cur, Token::RParen) {
+                return Err(ParseError::Unexpected {
+                    found: Token::Comma,
+                    expected: "an argument after `,` (no trailing comma)",
+                    span: Box::new(comma_span),
+                });
+            }
+        }
         self.expect(Token::RParen, grammar::RPAREN)?;
-        Ok(Stmt::Log(vec![Expr::Str(s)]))
+        Ok(args)
+    }
+
+    // parse an expression with operator precedence
+    pub fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+        Ok(self.parse_expr_bp(0)?.0)
+    }
+
+    // binding power of a binary operator, None if the token is not one
+    fn infix_bp(t: &Token) -> Option<u8> {
+        match t {
+            Token::Eq | Token::Ne | Token::Lt | Token::Le | Token::Gt | Token::Ge => Some(CMP_BP),
+            Token::Plus | Token::Minus => Some(10),
+            Token::Star | Token::Slash => Some(20),
+            _ => None,
+        }
+    }
+
+    // Pratt parser : read the operators binding tighter than min_bp,
+    // operators with the same power are left associative, except the
+    // comparisons : `a < b < c` is an error. The depth of the tree is
+    // returned with it: the later passes walk it recursively, so a long
+    // chain of operators is bounded like the parentheses.
+    fn parse_expr_bp(&mut self, min_bp: u8) -> Result<(Expr, usize), ParseError> {
+        let (mut left, mut depth) = self.parse_primary()?;
+        let mut compared = false; // a comparison was read at this level
+        while let Some(bp) = Self::infix_bp(&self.cur) {
+            if bp <= min_bp {
+                break;
+            }
+            if bp == CMP_BP {
+                if compared {
+                    return Err(ParseError::ChainedComparison {
+                        pos: self.cur_span.start.clone(),
+                    });
+                }
+                compared = true;
+            }
+            let op = self.cur.clone();
+            let op_pos = self.cur_span.start.clone();
+            self.bump()?; // operator
+            let (right, right_depth) = self.parse_expr_bp(bp)?;
+            depth = depth.max(right_depth) + 1;
+            if depth > MAX_NESTING {
+                return Err(ParseError::NestingTooDeep { pos: op_pos });
+            }
+            let (l, right) = (Box::new(left), Box::new(right));
+            left = match op {
+                Token::Plus => Expr::Add(l, right),
+                Token::Minus => Expr::Sub(l, right),
+                Token::Star => Expr::Mul(l, right),
+                Token::Slash => Expr::Div(l, right),
+                Token::Eq => Expr::Eq(l, right),
+                Token::Ne => Expr::Ne(l, right),
+                Token::Lt => Expr::Lt(l, right),
+                Token::Le => Expr::Le(l, right),
+                Token::Gt => Expr::Gt(l, right),
+                _ => Expr::Ge(l, right),
+            };
+        }
+        Ok((left, depth))
+    }
+
+    // primary := string | number | true | false | ident | '(' expr ')',
+    // with the depth of its tree
+    fn parse_primary(&mut self) -> Result<(Expr, usize), ParseError> {
+        if matches!(self.cur, Token::LParen) {
+            let e = self.nested(|p| {
+                p.bump()?; // '('
+                p.parse_expr_bp(0)
+            })?;
+            self.expect(Token::RParen, grammar::RPAREN)?;
+            return Ok(e);
+        }
+        Ok((self.parse_operand()?, 1))
+    }
+
+    // the leaves of an expression
+    fn parse_operand(&mut self) -> Result<Expr, ParseError> {
+        let pos = self.cur_span.start.clone();
+        match &self.cur {
+            Token::Str(txt) => {
+                let value = txt.clone();
+                self.bump()?; // eat the string
+                Ok(Expr::Str { value, pos })
+            }
+            Token::Number(n) => {
+                let value = n.parse::<i32>().map_err(|_| ParseError::IntOverflow {
+                    literal: self.lx.source(&self.cur_span).to_string(),
+                    span: Box::new(self.cur_span.clone()),
+                })?;
+                self.bump()?; // eat the number
+                Ok(Expr::Int { value, pos })
+            }
+            Token::Float(x) => {
+                let value = x.parse::<f64>().expect("the lexer only produces valid floats");
+                // too many digits : parsed as inf, which has no literal
+                if !value.is_finite() {
+                    return Err(ParseError::FloatOverflow {
+                        literal: self.lx.source(&self.cur_span).to_string(),
+                        span: Box::new(self.cur_span.clone()),
+                    });
+                }
+                self.bump()?; // eat the number
+                Ok(Expr::Float { value, pos })
+            }
+            Token::True | Token::False => {
+                let value = matches!(self.cur, Token::True);
+                self.bump()?; // eat the keyword
+                Ok(Expr::Bool { value, pos })
+            }
+            Token::Ident(name) => {
+                let out = name.clone();
+                self.bump()?; // eat the name
+                Ok(Expr::Var { name: out, pos })
+            }
+            _ => Err(ParseError::Unexpected {
+                found: self.cur.clone(),
+                expected: "an expression (string, number, boolean, variable or `(`)",
+                span: Box::new(self.cur_span.clone()),
+            }),
+        }
+    }
+
+    // an expression alone, the whole input
+    pub fn parse_whole_expr(&mut self) -> Result<Expr, ParseError> {
+        let e = self.parse_expr()?;
+        self.expect(Token::Eof, grammar::EOF)?;
+        Ok(e)
     }
 
-    // parse imported files (sub programs)
-    pub fn parse_sub_programs(&mut self) -> Result<Vec<Stmt>, ParseError> {
-        let mut stmts = Vec::new();
+    // parse imported files : imports, then a list of `fn name() { ... }`
+    pub fn parse_sub_functions(&mut self) -> Result<(Vec<Import>, Vec<Function>), ParseError> {
+        let imports = self.parse_imports()?;
+        let mut fns = Vec::new();
         while !matches!(self.cur, Token::Eof) {
-            // interdit explicitement tout `import` dans un fichier inclus
-            if matches!(self.cur, Token::Import) {
+            if !matches!(self.cur, Token::Fn) {
                 return Err(ParseError::Unexpected {
                     found: self.cur.clone(),
-                    expected: "no `import` in an included file (only in main program)",
-                    pos: self.cur_pos.clone(),
+                    expected: "`fn` (statements must be inside a function in an included file)",
+                    span: Box::new(self.cur_span.clone()),
                 });
             }
-            stmts.push(self.parse_stmt()?);
+            self.bump()?; // 'fn'
+            fns.push(self.parse_function()?);
         }
         self.expect(Token::Eof, grammar::EOF)?;
-        Ok(stmts)
+        Ok((imports, fns))
     }
 
-    // call <ident>()
+    // call <ident>(expr, expr, ...);
     fn parse_call(&mut self) -> Result<Stmt, ParseError> {
+        let pos = self.cur_span.start.clone();
         self.expect(Token::Call, crate::grammar::KW_CALL)?;
         // nom de fonction
         let name = if let Token::Ident(s) = &self.cur {
             let n = s.clone();
             self.bump()?;
             n
+        } else if matches!(self.cur, Token::Main) {
+            // parsed so that the resolver reports a clear error
+            self.bump()?;
+            grammar::KW_MAIN.to_string()
         } else {
             return Err(ParseError::Unexpected {
                 found: self.cur.clone(),
                 expected: "function name after `call`",
-                pos: self.cur_pos.clone(),
+                span: Box::new(self.cur_span.clone()),
             });
         };
+        let args = self.parse_args()?;
+        self.expect_semicolon()?;
+        Ok(Stmt::Call { name, args, pos })
+    }
+
+    // Parse `(a, b, ...)` : the parameter names, each one only once
+    fn parse_params(&mut self) -> Result<Vec<String>, ParseError> {
         self.expect(Token::LParen, crate::grammar::LPAREN)?;
+        let mut params: Vec<String> = Vec::new();
+        // a comma must be followed by another parameter
+        let mut more = !matches!(self.cur, Token::RParen);
+        while more {
+            let Token::Ident(name) = &self.cur else {
+                return Err(ParseError::Unexpected {
+                    found: self.cur.clone(),
+                    expected: "a parameter name",
+                    span: Box::new(self.cur_span.clone()),
+                });
+            };
+            if params.contains(name) {
+                return Err(ParseError::DuplicateParam {
+                    name: name.clone(),
+                    pos: self.cur_span.start.clone(),
+                });
+            }
+            params.push(name.clone());
+            self.bump()?; // name
+            more = matches!(self.cur, Token::Comma);
+            if more {
+                self.bump()?; // ','
+            }
+        }
         self.expect(Token::RParen, crate::grammar::RPAREN)?;
-        Ok(Stmt::Call { name })
+        Ok(params)
     }
 
     // Parse `(){ ... }` and return the vector stadment
     fn parse_fn_body_block(&mut self) -> Result<Vec<Stmt>, ParseError> {
         self.expect(Token::LParen, crate::grammar::LPAREN)?;
         self.expect(Token::RParen, crate::grammar::RPAREN)?;
+        self.parse_block()
+    }
+
+    // Parse `{ ... }` and return the vector stadment
+    fn parse_block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        self.nested(Self::parse_block_stmts)
+    }
+
+    fn parse_block_stmts(&mut self) -> Result<Vec<Stmt>, ParseError> {
         self.expect(Token::LBrace, crate::grammar::LBRACE)?;
         let mut body = Vec::new();
         while !matches!(self.cur, Token::RBrace) {
-            body.push(self.parse_stmt()?);
+            if self.recover && matches!(self.cur, Token::Eof | Token::Fn) {
+                // missing `}` : reported once, the block ends here
+                self.errors.push(ParseError::Unexpected {
+                    found: self.cur.clone(),
+                    expected: grammar::RBRACE,
+                    span: Box::new(self.cur_span.clone()),
+                });
+                return Ok(body);
+            }
+            match self.parse_stmt() {
+                Ok(stmt) => body.push(stmt),
+                Err(e) if self.recover => {
+                    self.errors.push(e);
+                    self.skip_until(Self::is_stmt_boundary);
+                }
+                Err(e) => return Err(e),
+            }
         }
         self.expect(Token::RBrace, crate::grammar::RBRACE)?;
         Ok(body)
     }
 
     fn parse_function(&mut self) -> Result<Function, ParseError> {
+        let pos = self.cur_span.start.clone();
         let name = if let Token::Ident(s) = &self.cur {
             let n = s.clone();
             self.bump()?;
@@ -225,19 +923,125 @@ impl<'a> Parser<'a> {
         } else if matches!(self.cur, Token::Main) {
             return Err(ParseError::Unexpected {
                 found: self.cur.clone(),
-                expected: "function (hors `main`)",
-                pos: self.cur_pos.clone(),
+                expected: "a function name other than `main`",
+                span: Box::new(self.cur_span.clone()),
             });
         } else {
             return Err(ParseError::Unexpected {
                 found: self.cur.clone(),
-                expected: "nom de fonction",
-                pos: self.cur_pos.clone(),
+                expected: "a function name",
+                span: Box::new(self.cur_span.clone()),
             });
         };
 
-        let body = self.parse_fn_body_block()?;
-        Ok(Function { name, body })
+        let params = self.parse_params()?;
+        let body = self.parse_block()?;
+        Ok(Function {
+            name,
+            params,
+            body,
+            pos,
+        })
+    }
+
+    // let <ident> = expr;
+    fn parse_let(&mut self) -> Result<Stmt, ParseError> {
+        let pos = self.cur_span.start.clone();
+        self.expect(Token::Let, grammar::KW_LET)?;
+        let name = if let Token::Ident(s) = &self.cur {
+            let n = s.clone();
+            self.bump()?;
+            n
+        } else {
+            return Err(ParseError::Unexpected {
+                found: self.cur.clone(),
+                expected: "a variable name after `let`",
+                span: Box::new(self.cur_span.clone()),
+            });
+        };
+        self.expect(Token::Assign, grammar::ASSIGN)?;
+        let value = self.parse_expr()?;
+        self.expect_semicolon()?;
+        Ok(Stmt::Let { name, value, pos })
+    }
+
+    // <ident> = expr; : the variable must already be declared with `let`
+    fn parse_assign(&mut self) -> Result<Stmt, ParseError> {
+        let pos = self.cur_span.start.clone();
+        let name = if let Token::Ident(s) = &self.cur {
+            let n = s.clone();
+            self.bump()?;
+            n
+        } else {
+            return Err(ParseError::Unexpected {
+                found: self.cur.clone(),
+                expected: "a variable name",
+                span: Box::new(self.cur_span.clone()),
+            });
+        };
+        self.expect(Token::Assign, grammar::ASSIGN)?;
+        let value = self.parse_expr()?;
+        self.expect_semicolon()?;
+        Ok(Stmt::Assign { name, value, pos })
+    }
+
+    // return; or return expr;
+    fn parse_return(&mut self) -> Result<Stmt, ParseError> {
+        let pos = self.cur_span.start.clone();
+        self.expect(Token::Return, grammar::KW_RETURN)?;
+        let value = if matches!(self.cur, Token::Semicolon) {
+            None
+        } else {
+            Some(self.parse_expr()?)
+        };
+        self.expect_semicolon()?;
+        Ok(Stmt::Return { value, pos })
+    }
+
+    // if expr { ... } [else { ... } | else if ...]
+    // The `else if` arms are read in a loop, not by recursion, so a long
+    // chain is not limited by MAX_NESTING. They are nested from the last one.
+    fn parse_if(&mut self) -> Result<Stmt, ParseError> {
+        let pos = self.cur_span.start.clone();
+        self.expect(Token::If, grammar::KW_IF)?;
+        let cond = self.parse_expr()?;
+        let then = self.parse_block()?;
+        let mut arms = Vec::new(); // (pos, cond, then) of each `else if`
+        let mut els = None;
+        while matches!(self.cur, Token::Else) {
+            self.bump()?; // 'else'
+            if !matches!(self.cur, Token::If) {
+                els = Some(self.parse_block()?);
+                break;
+            }
+            let pos = self.cur_span.start.clone();
+            self.bump()?; // 'if'
+            let cond = self.parse_expr()?;
+            arms.push((pos, cond, self.parse_block()?));
+        }
+        for (pos, cond, then) in arms.into_iter().rev() {
+            els = Some(vec![Stmt::If {
+                cond,
+                then,
+                els,
+                pos,
+            }]);
+        }
+        Ok(Stmt::If {
+            cond,
+            then,
+            els,
+            pos,
+        })
+    }
+
+    // while expr { ... }
+    fn parse_while(&mut self) -> Result<Stmt, ParseError> {
+        let pos = self.cur_span.start.clone();
+        self.expect(Token::While, grammar::KW_WHILE)?;
+        let cond = self.parse_expr()?;
+        let body = self.parse_block()?;
+        Ok(Stmt::While { cond, body, pos })
     }
 
     // parse a stadment
@@ -245,11 +1049,534 @@ impl<'a> Parser<'a> {
         match &self.cur {
             Token::Call => self.parse_call(),
             Token::Log => self.parse_log(),
+            Token::Let => self.parse_let(),
+            Token::Return => self.parse_return(),
+            Token::If => self.parse_if(),
+            Token::While => self.parse_while(),
+            Token::Else => Err(ParseError::Unexpected {
+                found: self.cur.clone(),
+                expected: "a statement (`else` must follow the block of an `if`)",
+                span: Box::new(self.cur_span.clone()),
+            }),
+            // a function is only called with `call`, a statement starting
+            // with a name is an assignment
+            Token::Ident(_) => self.parse_assign(),
             _ => Err(ParseError::Unexpected {
                 found: self.cur.clone(),
-                expected: "`log`",
-                pos: self.cur_pos.clone(),
+                expected: "`log`, `call`, `let`, `return`, `if`, `while` or an assignment",
+                span: Box::new(self.cur_span.clone()),
             }),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explain;
+
+    fn pos() -> Pos {
+        Pos {
+            byte: 0,
+            line: 1,
+            col: 1,
+            file: "t.gfr".into(),
+        }
+    }
+
+    fn int(n: i32) -> Box<Expr> {
+        Box::new(Expr::Int { value: n, pos: pos() })
+    }
+
+    fn text(s: &str) -> Box<Expr> {
+        Box::new(Expr::Str {
+            value: s.into(),
+            pos: pos(),
+        })
+    }
+
+    fn var(name: &str) -> Box<Expr> {
+        Box::new(Expr::Var {
+            name: name.into(),
+            pos: pos(),
+        })
+    }
+
+    #[test]
+    fn const_eval_of_each_expression() {
+        assert_eq!(text("a").const_eval(), Some(Value::Str("a".into())));
+        assert_eq!(int(7).const_eval(), Some(Value::Int(7)));
+        assert_eq!(var("x").const_eval(), None);
+        assert_eq!(Expr::Add(int(2), int(3)).const_eval(), Some(Value::Int(5)));
+        assert_eq!(Expr::Add(text("a"), text("b")).const_eval(), Some(Value::Str("ab".into())));
+        // a variable anywhere makes the whole expression non constant
+        assert_eq!(Expr::Add(int(1), var("x")).const_eval(), None);
+    }
+
+    #[test]
+    fn const_eval_rejects_overflow_and_mixed_types() {
+        assert_eq!(Expr::Add(int(i32::MAX), int(1)).const_eval(), None);
+        assert_eq!(Expr::Add(text("a"), int(1)).const_eval(), None);
+        assert_eq!(Expr::Add(int(1), text("a")).const_eval(), None);
+    }
+
+    #[test]
+    fn is_constant_follows_const_eval() {
+        assert!(Expr::Add(int(1), int(2)).is_constant());
+        assert!(!var("x").is_constant());
+        assert!(!Expr::Add(int(i32::MAX), int(1)).is_constant());
+    }
+
+    fn main_program_error(src: &str) -> ParseError {
+        Parser::new(Lexer::new(src))
+            .and_then(|mut p| p.parse_main_program())
+            .unwrap_err()
+    }
+
+    #[test]
+    fn empty_file_is_an_empty_program() {
+        let e = main_program_error("");
+        assert!(matches!(&e, ParseError::EmptyProgram { pos } if (pos.line, pos.col) == (1, 1)));
+        // only blanks: reported at the end of the file
+        let e = main_program_error("\n\n  ");
+        assert!(matches!(&e, ParseError::EmptyProgram { pos } if (pos.line, pos.col) == (3, 3)));
+    }
+
+    #[test]
+    fn imports_only_file_has_no_main() {
+        let e = main_program_error("import \"a.gfr\"\nimport \"b.gfr\"\n");
+        assert!(matches!(&e, ParseError::MissingMain { pos } if (pos.line, pos.col) == (3, 1)));
+        assert_eq!(e.code(), explain::E_MISSING_MAIN);
+    }
+
+    #[test]
+    fn unexpected_token_range_covers_the_token() {
+        let e = main_program_error("fn main() {\n  call \"f\"\n}");
+        // the string found instead of a function name
+        assert_eq!(e.byte_range(), 19..22);
+        let e = main_program_error("import \"a.gfr\"\n");
+        assert_eq!(e.byte_range(), 15..15); // the end of the file
+    }
+
+    #[test]
+    fn conditional_imports() {
+        let src = "import \"a.gfr\"\nimport \"d.gfr\" if DEBUG\nfn main() {}";
+        let (imports, _, _) = Parser::new(Lexer::new(src)).unwrap().parse_main_program().unwrap();
+        let imports: Vec<_> = imports
+            .iter()
+            .map(|i| (i.path.as_str(), i.cond.as_deref()))
+            .collect();
+        assert_eq!(imports, [("a.gfr", None), ("d.gfr", Some("DEBUG"))]);
+        let e = main_program_error("import \"d.gfr\" if \"DEBUG\"\nfn main() {}");
+        let ParseError::Unexpected { expected, .. } = e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!(expected, "a symbol name after `if`");
+    }
+
+    #[test]
+    fn trailing_comma_in_log_is_reported_at_the_comma() {
+        let e = main_program_error("fn main() {\n  log(\"a\", \"b\",);\n}");
+        let ParseError::Unexpected { found, span, .. } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!(*found, Token::Comma);
+        assert_eq!((span.start.line, span.start.col), (2, 15));
+        assert_eq!(e.byte_range(), 26..27);
+    }
+
+    #[test]
+    fn missing_semicolon_is_reported_after_the_statement() {
+        for stmt in ["log(1)", "call f()", "let x = 1"] {
+            let src = format!("fn main() {{\n  {}\n\n  log(2);\n}}", stmt);
+            let e = main_program_error(&src);
+            let ParseError::Unexpected { found, expected, span } = &e else {
+                panic!("unexpected error {:?}", e);
+            };
+            // just after the statement, not on the `log` two lines below
+            assert_eq!((found, *expected), (&Token::Log, ";"));
+            assert_eq!((span.start.line, span.start.col), (2, 3 + stmt.len()));
+            assert_eq!(e.byte_range(), 14 + stmt.len()..14 + stmt.len());
+        }
+    }
+
+    #[test]
+    fn int_overflow_is_reported_at_the_literal() {
+        let e = main_program_error("fn main() {\n  log(1 + 3000000000);\n}");
+        let ParseError::IntOverflow { literal, span } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!(literal, "3000000000");
+        assert_eq!((span.start.line, span.start.col), (2, 11));
+        assert_eq!((span.end.line, span.end.col), (2, 21));
+        let mut p = Parser::new(Lexer::new("2147483647")).unwrap();
+        assert!(matches!(p.parse_whole_expr(), Ok(Expr::Int { value: i32::MAX, .. })));
+    }
+
+    #[test]
+    fn float_literal_out_of_f64_range() {
+        let big = format!("1{}.5", "0".repeat(400));
+        let e = main_program_error(&format!("fn main() {{\n  log(2.0 * {});\n}}", big));
+        let ParseError::FloatOverflow { literal, span } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!(literal, &big);
+        assert_eq!((span.start.line, span.start.col), (2, 13));
+        assert_eq!(e.byte_range(), 24..24 + big.len());
+        assert!(e.to_string().ends_with(&format!("error[E0024]: float out of f64 range: {}", big)));
+        // the largest f64 is allowed
+        let max = format!("{}.0", f64::MAX);
+        let mut p = Parser::new(Lexer::new(&max)).unwrap();
+        assert!(matches!(p.parse_whole_expr(), Ok(Expr::Float { value: f64::MAX, .. })));
+        // a second point is not a float
+        let e = main_program_error("fn main() {\n  log(1.2.3);\n}");
+        assert!(matches!(&e, ParseError::Lex(l) if l.code == explain::E_MALFORMED_NUMBER), "{}", e);
+    }
+
+    #[test]
+    fn floats_are_printed_without_exponent() {
+        assert_eq!(float_text(1e20), "100000000000000000000.0");
+        assert_eq!(float_text(2.5), "2.5");
+        assert_eq!(float_text(3.0), "3.0");
+        assert_eq!(float_text(f64::INFINITY), "inf");
+        // read back as the same float
+        let text = float_text(1.5e300);
+        assert!(!text.contains('e'), "{}", text);
+        let mut p = Parser::new(Lexer::new(&text)).unwrap();
+        assert!(matches!(p.parse_whole_expr(), Ok(Expr::Float { value, .. }) if value == 1.5e300));
+    }
+
+    #[test]
+    fn overflowing_literals_keep_their_separators() {
+        let e = main_program_error("fn main() {\n  log(3_000_000_000);\n}");
+        let ParseError::IntOverflow { literal, .. } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!(literal, "3_000_000_000");
+        assert_eq!(e.byte_range(), 18..31);
+        let big = format!("1{}.0", "_000".repeat(110));
+        let e = main_program_error(&format!("fn main() {{\n  log({});\n}}", big));
+        let ParseError::FloatOverflow { literal, .. } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!(literal, &big);
+    }
+
+    #[test]
+    fn int_overflow_underlines_only_the_literal() {
+        let src = "fn main() {\n  log(2 * (1 + 99999999999) - 3);\n}";
+        let e = main_program_error(src);
+        assert!(matches!(e, ParseError::IntOverflow { .. }), "{}", e);
+        assert_eq!(e.byte_range(), 27..38);
+        assert_eq!(&src[e.byte_range()], "99999999999");
+    }
+
+    fn expr(src: &str) -> Expr {
+        Parser::new(Lexer::new(src)).unwrap().parse_whole_expr().unwrap()
+    }
+
+    // operators and operands of an arithmetic expression, in prefix form
+    fn tree(e: &Expr) -> String {
+        let (op, a, b) = match e {
+            Expr::Int { value: n, .. } => return n.to_string(),
+            Expr::Add(a, b) => ("+", a, b),
+            Expr::Sub(a, b) => ("-", a, b),
+            Expr::Mul(a, b) => ("*", a, b),
+            Expr::Div(a, b) => ("/", a, b),
+            Expr::Lt(a, b) => ("<", a, b),
+            Expr::Eq(a, b) => ("==", a, b),
+            _ => panic!("not arithmetic: {:?}", e),
+        };
+        format!("({} {} {})", op, tree(a), tree(b))
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        for (src, want) in [
+            ("2 + 3 * 4", "(+ 2 (* 3 4))"),
+            ("(2 + 3) * 4", "(* (+ 2 3) 4)"),
+            ("8 - 2 - 1", "(- (- 8 2) 1)"),
+            ("8 / 2 / 2", "(/ (/ 8 2) 2)"),
+            ("1 - 2 * 3 + 4 / 2", "(+ (- 1 (* 2 3)) (/ 4 2))"),
+        ] {
+            assert_eq!(tree(&expr(src)), want, "{}", src);
+        }
+    }
+
+    #[test]
+    fn expressions_start_at_their_first_token() {
+        let e = expr("  10 + x * 2.5");
+        let Expr::Add(a, b) = &e else {
+            panic!("not an addition: {:?}", e);
+        };
+        let col = |e: &Expr| (e.pos().line, e.pos().col);
+        assert_eq!((col(&e), col(a), col(b)), ((1, 3), (1, 3), (1, 8)));
+        let Expr::Mul(_, f) = &**b else {
+            panic!("not a product: {:?}", b);
+        };
+        assert_eq!(col(f), (1, 12));
+        assert_eq!(col(&expr("\n \"s\"")), (2, 2));
+        assert_eq!(col(&expr("\n\n   true")), (3, 4));
+    }
+
+    #[test]
+    fn const_eval_of_integer_operators() {
+        assert_eq!(expr("7 - 2 * 3").const_eval(), Some(Value::Int(1)));
+        assert_eq!(expr("7 / 2").const_eval(), Some(Value::Int(3)));
+        assert_eq!(expr("1 - 2").const_eval(), Some(Value::Int(-1)));
+        // no value for a division by zero or an overflow
+        assert_eq!(expr("1 / 0").const_eval(), None);
+        assert_eq!(expr("65536 * 65536").const_eval(), None);
+        assert_eq!(expr("0 - 2147483647 - 2").const_eval(), None);
+        // only integers: no string difference
+        assert_eq!(expr("\"a\" - \"b\"").const_eval(), None);
+    }
+
+    #[test]
+    fn unclosed_parenthesis() {
+        let e = Parser::new(Lexer::new("(1 + 2")).unwrap().parse_whole_expr().unwrap_err();
+        assert!(matches!(e, ParseError::Unexpected { found: Token::Eof, .. }));
+    }
+
+    #[test]
+    fn messages_are_in_english() {
+        let e = main_program_error("fn main() {}\nfn main() {}");
+        assert!(e.to_string().contains("a function name other than `main`"), "{}", e);
+        let e = main_program_error("fn main() {\n  log(3000000000);\n}");
+        assert!(e.to_string().ends_with("integer out of i32 range: 3000000000"), "{}", e);
+    }
+
+    #[test]
+    fn const_eval_of_floats() {
+        assert_eq!(expr("1.5 * 2.0 - 0.5").const_eval(), Some(Value::Float(2.5)));
+        assert_eq!(expr("1.0 / 0.0").const_eval(), Some(Value::Float(f64::INFINITY)));
+        // no implicit conversion between integers and floats
+        assert_eq!(expr("1 + 1.5").const_eval(), None);
+    }
+
+    #[test]
+    fn hexadecimal_overflow() {
+        let src = "fn main() {\n  log(0x100000000);\n}";
+        let e = main_program_error(src);
+        assert!(matches!(e, ParseError::IntOverflow { .. }), "{}", e);
+        // the span covers the 0x prefix too
+        assert_eq!(&src[e.byte_range()], "0x100000000");
+        assert!(e.to_string().ends_with("integer out of i32 range: 0x100000000"), "{}", e);
+        assert_eq!(expr("0x7FFFFFFF").const_eval(), Some(Value::Int(i32::MAX)));
+    }
+
+    fn functions(src: &str) -> Result<Vec<Function>, ParseError> {
+        Ok(Parser::new(Lexer::new(src))?.parse_sub_functions()?.1)
+    }
+
+    #[test]
+    fn function_parameters() {
+        let fns = functions("fn f() {}\nfn g(a) {}\nfn h(a, b, c) {}").unwrap();
+        let params: Vec<_> = fns.iter().map(|f| f.params.join(",")).collect();
+        assert_eq!(params, ["", "a", "a,b,c"]);
+        // a comma must be followed by another parameter
+        let e = functions("fn f(a,) {}").unwrap_err();
+        assert!(matches!(e, ParseError::Unexpected { found: Token::RParen, .. }), "{}", e);
+    }
+
+    #[test]
+    fn duplicate_parameter_is_reported_at_the_second_one() {
+        let e = functions("fn f(a, b,\n     a) {}").unwrap_err();
+        let ParseError::DuplicateParam { name, pos } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!((name.as_str(), pos.line, pos.col), ("a", 2, 6));
+        assert_eq!(e.byte_range(), 16..17);
+    }
+
+    #[test]
+    fn if_else_and_else_if() {
+        let (_, prog, _) = Parser::new(Lexer::new(
+            "fn main() {\n  if 1 {\n    log(1);\n  } else if 2 {\n  } else {\n    log(3);\n  }\n}",
+        ))
+        .unwrap()
+        .parse_main_program()
+        .unwrap();
+        let [Stmt::If { then, els: Some(els), .. }] = prog.stmts.as_slice() else {
+            panic!("unexpected statements {:?}", prog.stmts);
+        };
+        assert_eq!(then.len(), 1);
+        // else if: an else block holding only the second if
+        let [Stmt::If { then, els: Some(last), .. }] = els.as_slice() else {
+            panic!("unexpected else {:?}", els);
+        };
+        assert!(then.is_empty());
+        assert!(matches!(last.as_slice(), [Stmt::Log { .. }]));
+    }
+
+    #[test]
+    fn dangling_else_is_reported_at_the_else() {
+        let e = main_program_error("fn main() {\n  log(1);\n  else {\n  }\n}");
+        let ParseError::Unexpected { found, span, .. } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!((found, span.start.line, span.start.col), (&Token::Else, 3, 3));
+        assert!(e.to_string().contains("`else` must follow the block of an `if`"), "{}", e);
+    }
+
+    fn nesting_error(e: ParseError) -> (usize, usize) {
+        match e {
+            ParseError::NestingTooDeep { pos } => (pos.line, pos.col),
+            _ => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn deep_parentheses_are_an_error_not_a_crash() {
+        let deep = |n| format!("{}1{}", "(".repeat(n), ")".repeat(n));
+        let e = Parser::new(Lexer::new(&deep(100_000))).unwrap().parse_whole_expr();
+        // reported at the first `(` one level too deep
+        assert_eq!(nesting_error(e.unwrap_err()), (1, MAX_NESTING + 1));
+        assert!(Parser::new(Lexer::new(&deep(MAX_NESTING))).unwrap().parse_whole_expr().is_ok());
+    }
+
+    #[test]
+    fn long_operator_chain_is_bounded() {
+        // no parenthesis but a tree as deep as the chain is long
+        let chain = |n| format!("1{}", " + 1".repeat(n));
+        let e = Parser::new(Lexer::new(&chain(3000))).unwrap().parse_whole_expr();
+        // the `+` at byte 4 * MAX_NESTING - 2 makes the tree one level too deep
+        assert_eq!(nesting_error(e.unwrap_err()), (1, 4 * MAX_NESTING - 1));
+        let e = expr(&chain(MAX_NESTING - 1));
+        assert_eq!(e.const_eval(), Some(Value::Int(MAX_NESTING as i32)));
+    }
+
+    #[test]
+    fn deep_blocks_are_an_error() {
+        let deep = |n| format!("fn main() {{\n{}{}\n}}", "if 1 {".repeat(n), "}".repeat(n));
+        // the function body is the first block, the last `{` is one too many
+        let e = main_program_error(&deep(MAX_NESTING));
+        assert_eq!(nesting_error(e), (2, 6 * MAX_NESTING));
+        assert!(Parser::new(Lexer::new(&deep(MAX_NESTING - 1)))
+            .and_then(|mut p| p.parse_main_program())
+            .is_ok());
+    }
+
+    #[test]
+    fn long_else_if_chain_is_not_nesting() {
+        let n = 3 * MAX_NESTING;
+        let arms = (1..n).map(|i| format!(" else if {} {{}}", i)).collect::<String>();
+        let src = format!("fn main() {{\n  if 0 {{}}{} else {{\n    log(1);\n  }}\n}}", arms);
+        let (_, prog, _) = Parser::new(Lexer::new(&src)).unwrap().parse_main_program().unwrap();
+        // one `if` per arm, each in the `else` of the previous one
+        let mut stmt = &prog.stmts[0];
+        for i in 1..n {
+            let Stmt::If { els: Some(els), .. } = stmt else {
+                panic!("arm {} is not an else: {:?}", i, stmt);
+            };
+            stmt = &els[0];
+            let Stmt::If { cond, .. } = stmt else {
+                panic!("arm {} is not an if: {:?}", i, stmt);
+            };
+            assert_eq!(cond.const_eval(), Some(Value::Int(i as i32)));
+        }
+        let Stmt::If { els: Some(els), .. } = stmt else {
+            panic!("no final else: {:?}", stmt);
+        };
+        assert!(matches!(els.as_slice(), [Stmt::Log { .. }]));
+    }
+
+    #[test]
+    fn comparisons_bind_below_arithmetic() {
+        assert_eq!(tree(&expr("1 + 2 < 3 * 4")), "(< (+ 1 2) (* 3 4))");
+        assert_eq!(tree(&expr("(1 < 2) == 1")), "(== (< 1 2) 1)");
+    }
+
+    #[test]
+    fn chained_comparison_is_reported_at_the_second_operator() {
+        let e = main_program_error("fn main() {\n  log(a < b < c);\n}");
+        let ParseError::ChainedComparison { pos } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!((pos.line, pos.col), (2, 13));
+        let e = main_program_error("fn main() {\n  log(1 == 2 != 3);\n}");
+        assert!(matches!(e, ParseError::ChainedComparison { .. }), "{}", e);
+    }
+
+    #[test]
+    fn const_eval_of_comparisons() {
+        for (src, want) in [
+            ("1 < 2", 1),
+            ("2 <= 1", 0),
+            ("3 > 3", 0),
+            ("3 >= 3", 1),
+            ("1 + 1 == 2", 1),
+            ("1 != 1", 0),
+            ("\"a\" < \"b\"", 1),
+            ("1.5 > 0.5", 1),
+        ] {
+            assert_eq!(expr(src).const_eval(), Some(Value::Bool(want == 1)), "{}", src);
+        }
+        // NaN is not ordered: no value at compile time
+        assert_eq!(expr("0.0 / 0.0 == 0.0 / 0.0").const_eval(), None);
+        assert_eq!(expr("1 < 1.5").const_eval(), None);
+    }
+
+    #[test]
+    fn const_eval_of_booleans() {
+        assert_eq!(expr("true").const_eval(), Some(Value::Bool(true)));
+        assert_eq!(expr("true == (1 < 0)").const_eval(), Some(Value::Bool(false)));
+        assert_eq!(expr("false < true").const_eval(), Some(Value::Bool(true)));
+        // booleans are not integers
+        assert_eq!(expr("true + 1").const_eval(), None);
+        assert_eq!(expr("true == 1").const_eval(), None);
+        assert!(expr("false").is_bool() && expr("1 < 2").is_bool());
+        assert!(!expr("1 + 2").is_bool());
+    }
+
+    fn recover(src: &str) -> (Program, Vec<ParseError>) {
+        Parser::new(Lexer::new(src)).unwrap().parse_main_program_recover()
+    }
+
+    // line and column of each error
+    fn positions(errors: &[ParseError]) -> Vec<(usize, usize)> {
+        errors
+            .iter()
+            .map(|e| match e {
+                ParseError::Unexpected { span, .. } => (span.start.line, span.start.col),
+                ParseError::IntOverflow { span, .. } => (span.start.line, span.start.col),
+                ParseError::Lex(e) => (e.pos.line, e.pos.col),
+                _ => panic!("unexpected error {:?}", e),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recovery_goes_on_at_the_next_statement() {
+        let src = "fn main() {\n  log(1 +);\n  log(\"ok\");\n  let = 2;\n  log(# 3);\n}";
+        let (prog, errors) = recover(src);
+        assert_eq!(positions(&errors), [(2, 10), (4, 7), (5, 7)]);
+        assert!(matches!(errors[2], ParseError::Lex(_)));
+        // the statements without error are kept
+        assert!(matches!(prog.stmts.as_slice(), [Stmt::Log { .. }, Stmt::Log { .. }]), "{:?}", prog);
+    }
+
+    #[test]
+    fn recovery_goes_on_at_the_next_function() {
+        let src = "fn f( {\n}\nfn main() {\n  log(1);\n";
+        let (prog, errors) = recover(src);
+        let ParseError::Unexpected { found, expected, .. } = &errors[1] else {
+            panic!("unexpected errors {:?}", errors);
+        };
+        // missing `}` at the end of the file, reported once
+        assert_eq!((found, *expected), (&Token::Eof, grammar::RBRACE));
+        assert_eq!(positions(&errors), [(1, 7), (5, 1)]);
+        assert_eq!(prog.stmts.len(), 1);
+    }
+
+    #[test]
+    fn recovery_of_a_valid_or_empty_program() {
+        let (prog, errors) = recover("fn main() {\n  log(1);\n}\nfn f() {}");
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(prog.stmts.len(), 1);
+        let (_, errors) = recover("");
+        assert!(matches!(errors.as_slice(), [ParseError::EmptyProgram { .. }]));
+        let (_, errors) = recover("fn f() {}");
+        assert!(matches!(errors.as_slice(), [ParseError::MissingMain { .. }]));
+    }
+}
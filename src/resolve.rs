@@ -0,0 +1,373 @@
+use crate::error::CompileError;
+use crate::grammar;
+use crate::parser::{visit_stmts, Expr, Function, Program, Stmt};
+
+use std::collections::{HashMap, HashSet};
+
+// Every check records its errors and goes on. The resolve_* passes stop at
+// the first one, resolve_all reports all of them for --fail-fast=false.
+type Errors = Vec<CompileError>;
+
+fn first(errors: Errors) -> Result<(), CompileError> {
+    errors.into_iter().next().map_or(Ok(()), Err)
+}
+
+// the errors of the three passes, in their order. A variable is reported
+// once in its block : its other uses would only repeat the error.
+pub fn resolve_all(main: &Program, fns: &[Function]) -> Errors {
+    let mut errors = Vec::new();
+    check_calls(main, fns, &mut errors);
+    check_all_vars(main, fns, &mut errors);
+    check_returns(main, fns, &mut errors);
+    errors
+}
+
+// check that every `call` refers to a defined function, with one argument
+// per parameter
+pub fn resolve_calls(main: &Program, fns: &[Function]) -> Result<(), CompileError> {
+    let mut errors = Vec::new();
+    check_calls(main, fns, &mut errors);
+    first(errors)
+}
+
+fn check_calls(main: &Program, fns: &[Function], errors: &mut Errors) {
+    let defs: HashMap<&str, &Function> = fns.iter().map(|f| (f.name.as_str(), f)).collect();
+    for f in fns {
+        check_stmts(&f.body, &defs, errors);
+    }
+    check_stmts(&main.stmts, &defs, errors);
+}
+
+fn check_stmts(stmts: &[Stmt], defs: &HashMap<&str, &Function>, errors: &mut Errors) {
+    let mut calls = Vec::new();
+    visit_stmts(stmts, &mut |s| calls.push(s));
+    for stmt in calls {
+        if let Stmt::Call { name, args, pos } = stmt {
+            if name == grammar::KW_MAIN {
+                errors.push(CompileError::CallMain { pos: pos.clone() });
+                continue;
+            }
+            let Some(def) = defs.get(name.as_str()) else {
+                errors.push(CompileError::UnknownFunction {
+                    name: name.clone(),
+                    pos: pos.clone(),
+                });
+                continue;
+            };
+            if args.len() != def.params.len() {
+                errors.push(CompileError::ArgCount {
+                    name: name.clone(),
+                    expected: def.params.len(),
+                    found: args.len(),
+                    call: pos.clone(),
+                    def: Box::new(def.pos.clone()),
+                });
+            }
+        }
+    }
+}
+
+// check that every variable is declared by a `let` before being used
+pub fn resolve_vars(main: &Program, fns: &[Function]) -> Result<(), CompileError> {
+    let mut errors = Vec::new();
+    check_all_vars(main, fns, &mut errors);
+    first(errors)
+}
+
+fn check_all_vars(main: &Program, fns: &[Function], errors: &mut Errors) {
+    for f in fns {
+        check_vars(&f.params, &f.body, errors);
+    }
+    check_vars(&[], &main.stmts, errors);
+}
+
+// the parameters are visible in the whole function, the variables from
+// their `let` to the end of the function
+fn check_vars(params: &[String], stmts: &[Stmt], errors: &mut Errors) {
+    check_block(stmts, params.iter().map(String::as_str).collect(), errors);
+}
+
+// the variables declared in a block are not visible after it. An unknown
+// variable is declared once reported.
+fn check_block<'a>(stmts: &'a [Stmt], mut declared: HashSet<&'a str>, errors: &mut Errors) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Log { args, .. } | Stmt::Call { args, .. } => {
+                for arg in args {
+                    check_expr(arg, &mut declared, errors);
+                }
+            }
+            Stmt::Let { name, value, .. } => {
+                // the value is checked first : `let x = x;` needs an older x
+                check_expr(value, &mut declared, errors);
+                declared.insert(name.as_str());
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    check_expr(value, &mut declared, errors);
+                }
+            }
+            Stmt::Assign { name, value, pos } => {
+                check_expr(value, &mut declared, errors);
+                if declared.insert(name.as_str()) {
+                    errors.push(CompileError::UnknownVariable {
+                        name: name.clone(),
+                        pos: pos.clone(),
+                    });
+                }
+            }
+            Stmt::If { .. } => {
+                let (arms, last) = stmt.if_arms();
+                for (_, cond, then) in arms {
+                    check_expr(cond, &mut declared, errors);
+                    check_block(then, declared.clone(), errors);
+                }
+                if let Some(els) = last {
+                    check_block(els, declared.clone(), errors);
+                }
+            }
+            Stmt::While { cond, body, .. } => {
+                check_expr(cond, &mut declared, errors);
+                check_block(body, declared.clone(), errors);
+            }
+        }
+    }
+}
+
+fn check_expr<'a>(e: &'a Expr, declared: &mut HashSet<&'a str>, errors: &mut Errors) {
+    match e {
+        Expr::Str { .. } | Expr::Int { .. } | Expr::Float { .. } | Expr::Bool { .. } => {}
+        Expr::Var { name, pos } => {
+            if declared.insert(name.as_str()) {
+                errors.push(CompileError::UnknownVariable {
+                    name: name.clone(),
+                    pos: pos.clone(),
+                });
+            }
+        }
+        Expr::Add(a, b)
+        | Expr::Sub(a, b)
+        | Expr::Mul(a, b)
+        | Expr::Div(a, b)
+        | Expr::Eq(a, b)
+        | Expr::Ne(a, b)
+        | Expr::Lt(a, b)
+        | Expr::Le(a, b)
+        | Expr::Gt(a, b)
+        | Expr::Ge(a, b) => {
+            check_expr(a, declared, errors);
+            check_expr(b, declared, errors);
+        }
+    }
+}
+
+// a function returns a value with every `return` or with none of them,
+// `main` never returns a value
+pub fn resolve_returns(main: &Program, fns: &[Function]) -> Result<(), CompileError> {
+    let mut errors = Vec::new();
+    check_returns(main, fns, &mut errors);
+    first(errors)
+}
+
+fn check_returns(main: &Program, fns: &[Function], errors: &mut Errors) {
+    let mut stmts = Vec::new();
+    visit_stmts(&main.stmts, &mut |s| stmts.push(s));
+    for stmt in stmts {
+        if let Stmt::Return { value: Some(_), pos } = stmt {
+            errors.push(CompileError::MainReturnValue { pos: pos.clone() });
+        }
+    }
+    for f in fns {
+        let mut with_value = None; // kind of the first `return`
+        let mut stmts = Vec::new();
+        visit_stmts(&f.body, &mut |s| stmts.push(s));
+        for stmt in stmts {
+            if let Stmt::Return { value, pos } = stmt {
+                let first = *with_value.get_or_insert(value.is_some());
+                if first != value.is_some() {
+                    // one error per function
+                    errors.push(CompileError::MixedReturn {
+                        name: f.name.clone(),
+                        pos: pos.clone(),
+                    });
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explain;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    // resolve a main file and one imported file
+    fn resolve(main: &str, lib: &str) -> Result<(), CompileError> {
+        let (_, main, mut fns) =
+            Parser::new(Lexer::with_file("main.gfr", main))?.parse_main_program()?;
+        fns.extend(Parser::new(Lexer::with_file("lib.gfr", lib))?.parse_sub_functions()?.1);
+        resolve_calls(&main, &fns)?;
+        resolve_vars(&main, &fns)?;
+        resolve_returns(&main, &fns)
+    }
+
+    #[test]
+    fn calls_to_defined_functions() {
+        let lib = "fn a() {\n  call b();\n}\nfn b() {}";
+        assert!(resolve("fn main() {\n  call a();\n}", lib).is_ok());
+    }
+
+    #[test]
+    fn unknown_function_is_reported_at_the_call() {
+        let e = resolve("fn main() {\n  log(\"x\");\n  call g();\n}", "fn f() {}").unwrap_err();
+        let CompileError::UnknownFunction { name, pos } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!((name.as_str(), pos.line, pos.col), ("g", 3, 3));
+        assert_eq!(e.to_string(), "main.gfr:3:3: error[E0007]: call to unknown function `g`");
+        // in an imported function too
+        let e = resolve("fn main() {}", "fn f() {\n    call h();\n}").unwrap_err();
+        assert!(matches!(&e, CompileError::UnknownFunction { pos, .. } if pos.file == "lib.gfr"));
+    }
+
+    #[test]
+    fn main_cannot_be_called() {
+        let e = resolve("fn main() {}", "fn f() {\n  call main();\n}").unwrap_err();
+        let CompileError::CallMain { pos } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!((pos.file.as_str(), pos.line, pos.col), ("lib.gfr", 2, 3));
+    }
+
+    #[test]
+    fn variables_are_visible_after_their_let() {
+        let main = "fn main() {\n  let x = 1;\n  let y = x + 1;\n  log(x, y);\n}";
+        assert!(resolve(main, "fn f() {\n  let x = 2;\n  log(x);\n}").is_ok());
+    }
+
+    #[test]
+    fn unknown_variable_is_reported_at_its_use() {
+        let e = resolve("fn main() {\n  log(1 + x);\n  let x = 1;\n}", "").unwrap_err();
+        let CompileError::UnknownVariable { name, pos } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!((name.as_str(), pos.line, pos.col), ("x", 2, 11));
+        // `let x = x;` needs an older x
+        let e = resolve("fn main() {\n  let x = x;\n}", "").unwrap_err();
+        assert!(matches!(&e, CompileError::UnknownVariable { pos, .. } if pos.col == 11));
+        // the variables of main are not visible in the other functions
+        let e = resolve("fn main() {\n  let x = 1;\n}", "fn f() {\n  log(x);\n}").unwrap_err();
+        assert!(matches!(&e, CompileError::UnknownVariable { pos, .. } if pos.file == "lib.gfr"));
+    }
+
+    #[test]
+    fn assignment_to_an_undeclared_variable() {
+        let main = "fn main() {\n  let x = 1;\n  x = x + 1;\n  log(x);\n}";
+        assert!(resolve(main, "").is_ok());
+        let e = resolve("fn main() {\n  let x = 1;\n  y = x;\n}", "").unwrap_err();
+        let CompileError::UnknownVariable { name, pos } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        // reported at the assigned name
+        assert_eq!((name.as_str(), pos.line, pos.col), ("y", 3, 3));
+    }
+
+    #[test]
+    fn parameters_are_visible_in_the_whole_function() {
+        assert!(resolve("fn main() {}", "fn f(a, b) {\n  log(a + b);\n}").is_ok());
+        let e = resolve("fn main() {}", "fn f(a) {\n  log(b);\n}").unwrap_err();
+        assert!(matches!(&e, CompileError::UnknownVariable { name, .. } if name == "b"));
+    }
+
+    #[test]
+    fn argument_count_must_match_the_parameters() {
+        let lib = "fn add(a, b) {\n  log(a + b);\n}";
+        assert!(resolve("fn main() {\n  call add(1, 2);\n}", lib).is_ok());
+        let e = resolve("fn main() {\n  call add(1);\n}", lib).unwrap_err();
+        let CompileError::ArgCount {
+            name,
+            expected,
+            found,
+            call,
+            def,
+        } = &e
+        else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!((name.as_str(), *expected, *found), ("add", 2, 1));
+        assert_eq!((call.file.as_str(), call.line, call.col), ("main.gfr", 2, 3));
+        assert_eq!((def.file.as_str(), def.line, def.col), ("lib.gfr", 1, 4));
+    }
+
+    #[test]
+    fn returns_with_and_without_a_value_are_not_mixed() {
+        let lib = "fn f(n) {\n  return n;\n  return 0;\n}\nfn g() {\n  return;\n}";
+        assert!(resolve("fn main() {\n  return;\n}", lib).is_ok());
+        let lib = "fn f(n) {\n  return n;\n  log(n);\n  return;\n}";
+        let e = resolve("fn main() {}", lib).unwrap_err();
+        let CompileError::MixedReturn { name, pos } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        // reported at the `return` that does not match the first one
+        assert_eq!((name.as_str(), pos.line, pos.col), ("f", 4, 3));
+    }
+
+    #[test]
+    fn main_cannot_return_a_value() {
+        let e = resolve("fn main() {\n  log(1);\n  return 1;\n}", "").unwrap_err();
+        let CompileError::MainReturnValue { pos } = &e else {
+            panic!("unexpected error {:?}", e);
+        };
+        assert_eq!((pos.file.as_str(), pos.line, pos.col), ("main.gfr", 3, 3));
+    }
+
+    #[test]
+    fn variables_of_a_block_are_not_visible_after_it() {
+        let main = "fn main() {\n  let x = 1;\n  if x {\n    let y = x;\n    log(y);\n  }\n}";
+        assert!(resolve(main, "").is_ok());
+        let main = "fn main() {\n  if 1 {\n    let y = 1;\n  } else {\n    log(y);\n  }\n}";
+        let e = resolve(main, "").unwrap_err();
+        assert!(matches!(&e, CompileError::UnknownVariable { pos, .. } if pos.line == 5), "{}", e);
+        // a call nested in a block is checked too
+        let e = resolve("fn main() {\n  if 1 {\n    call g();\n  }\n}", "").unwrap_err();
+        assert!(matches!(&e, CompileError::UnknownFunction { pos, .. } if pos.line == 3));
+    }
+
+    #[test]
+    fn loop_body_is_a_block() {
+        let main = "fn main() {\n  let n = 2;\n  while n {\n    let m = n;\n    n = m - 1;\n  }\n}";
+        assert!(resolve(main, "").is_ok());
+        let main = "fn main() {\n  while 1 {\n    let m = 1;\n  }\n  log(m);\n}";
+        let e = resolve(main, "").unwrap_err();
+        assert!(matches!(&e, CompileError::UnknownVariable { pos, .. } if pos.line == 5), "{}", e);
+    }
+
+    #[test]
+    fn independent_errors_are_all_reported() {
+        let main = "fn main() {\n  call g();\n  log(x, x);\n  x = 1;\n  call f();\n  return 1;\n}";
+        let lib = "fn f(a) {\n  log(y);\n  return a;\n  return;\n  return;\n}";
+        let parser = |file, src| Parser::new(Lexer::with_file(file, src)).unwrap();
+        let (_, main, mut fns) = parser("main.gfr", main).parse_main_program().unwrap();
+        fns.extend(parser("lib.gfr", lib).parse_sub_functions().unwrap().1);
+        let errors: Vec<_> = resolve_all(&main, &fns)
+            .iter()
+            .map(|e| (e.to_string().split(": error").next().unwrap().to_string(), e.code()))
+            .collect();
+        // x is reported once, f once for its two `return;`
+        let expected = [
+            ("main.gfr:2:3", explain::E_UNKNOWN_FUNCTION),
+            ("main.gfr:5:3", explain::E_ARG_COUNT),
+            ("lib.gfr:2:7", explain::E_UNKNOWN_VARIABLE),
+            ("main.gfr:3:7", explain::E_UNKNOWN_VARIABLE),
+            ("main.gfr:6:3", explain::E_MAIN_RETURN_VALUE),
+            ("lib.gfr:4:3", explain::E_MIXED_RETURN),
+        ];
+        assert_eq!(errors, expected.map(|(pos, code)| (pos.to_string(), code)));
+        // the passes stop at their first error
+        let e = resolve_calls(&main, &fns).unwrap_err();
+        assert!(matches!(e, CompileError::UnknownFunction { .. }), "{}", e);
+    }
+}
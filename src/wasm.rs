@@ -0,0 +1,787 @@
+use crate::error::CompileError;
+
+use std::collections::HashMap;
+
+// Assemble the WAT text written by codegen into a binary wasm module. Only the
+// part of the text format used by codegen is understood : flat and folded
+// instructions, named functions, locals and labels, i32 values.
+
+const MAGIC: &[u8] = b"\0asm";
+const VERSION: &[u8] = &[1, 0, 0, 0];
+
+// section ids, in the order they must appear
+const SEC_TYPE: u8 = 1;
+const SEC_IMPORT: u8 = 2;
+const SEC_FUNCTION: u8 = 3;
+const SEC_MEMORY: u8 = 5;
+const SEC_EXPORT: u8 = 7;
+const SEC_CODE: u8 = 10;
+const SEC_DATA: u8 = 11;
+
+const FUNC_TYPE: u8 = 0x60;
+const I32: u8 = 0x7F;
+const EMPTY_BLOCK: u8 = 0x40; // block type of a block without result
+const EXPORT_FUNC: u8 = 0x00;
+const EXPORT_MEMORY: u8 = 0x02;
+
+// instructions without immediate
+const PLAIN_OPS: &[(&str, u8)] = &[
+    ("unreachable", 0x00),
+    ("nop", 0x01),
+    ("return", 0x0F),
+    ("drop", 0x1A),
+    ("i32.eqz", 0x45),
+    ("i32.eq", 0x46),
+    ("i32.ne", 0x47),
+    ("i32.lt_s", 0x48),
+    ("i32.lt_u", 0x49),
+    ("i32.gt_s", 0x4A),
+    ("i32.gt_u", 0x4B),
+    ("i32.le_s", 0x4C),
+    ("i32.le_u", 0x4D),
+    ("i32.ge_s", 0x4E),
+    ("i32.ge_u", 0x4F),
+    ("i32.add", 0x6A),
+    ("i32.sub", 0x6B),
+    ("i32.mul", 0x6C),
+    ("i32.div_s", 0x6D),
+    ("i32.div_u", 0x6E),
+    ("i32.rem_s", 0x6F),
+    ("i32.rem_u", 0x70),
+];
+
+// memory instructions : (name, opcode, log2 of the natural alignment)
+const MEMORY_OPS: &[(&str, u8, u32)] = &[
+    ("i32.load", 0x28, 2),
+    ("i32.load8_u", 0x2D, 0),
+    ("i32.store", 0x36, 2),
+    ("i32.store8", 0x3A, 0),
+];
+
+// a node of the text format
+enum Sexp {
+    Atom(String),
+    Str(Vec<u8>),
+    List(Vec<Sexp>),
+}
+
+impl Sexp {
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Sexp::Atom(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    // (keyword ...) : the items after the keyword
+    fn form(&self, keyword: &str) -> Option<&[Sexp]> {
+        match self {
+            Sexp::List(items) if items.first().and_then(Sexp::atom) == Some(keyword) => {
+                Some(&items[1..])
+            }
+            _ => None,
+        }
+    }
+}
+
+fn unsupported(what: impl std::fmt::Display) -> CompileError {
+    CompileError::Unsupported {
+        what: format!("{} in wasm output", what),
+    }
+}
+
+// read the text into s-expressions, skipping `;;` comments
+struct Reader<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn skip_blank(&mut self) {
+        while let Some(&b) = self.src.get(self.pos) {
+            if b.is_ascii_whitespace() {
+                self.pos += 1;
+            } else if self.src[self.pos..].starts_with(b";;") {
+                while self.pos < self.src.len() && self.src[self.pos] != b'\n' {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn sexp(&mut self) -> Result<Sexp, CompileError> {
+        match self.src[self.pos] {
+            b'(' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_blank();
+                    match self.src.get(self.pos) {
+                        None => return Err(unsupported("unclosed `(`")),
+                        Some(b')') => break,
+                        Some(_) => items.push(self.sexp()?),
+                    }
+                }
+                self.pos += 1;
+                Ok(Sexp::List(items))
+            }
+            b')' => Err(unsupported("unmatched `)`")),
+            b'"' => self.string(),
+            _ => {
+                let start = self.pos;
+                while let Some(&b) = self.src.get(self.pos) {
+                    if b.is_ascii_whitespace() || matches!(b, b'(' | b')' | b'"' | b';') {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                Ok(Sexp::Atom(
+                    String::from_utf8_lossy(&self.src[start..self.pos]).into_owned(),
+                ))
+            }
+        }
+    }
+
+    // "..." with the \xx hexadecimal escapes used for data segments
+    fn string(&mut self) -> Result<Sexp, CompileError> {
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            match self.src.get(self.pos) {
+                None => return Err(unsupported("unterminated string")),
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    let esc = self.src.get(self.pos + 1..self.pos + 3);
+                    let byte = esc
+                        .and_then(|h| std::str::from_utf8(h).ok())
+                        .and_then(|h| u8::from_str_radix(h, 16).ok());
+                    match byte {
+                        Some(byte) => {
+                            bytes.push(byte);
+                            self.pos += 3;
+                        }
+                        None => {
+                            let (byte, len) = match self.src.get(self.pos + 1) {
+                                Some(b'n') => (b'\n', 2),
+                                Some(b't') => (b'\t', 2),
+                                Some(b'r') => (b'\r', 2),
+                                Some(&c @ (b'\\' | b'"' | b'\'')) => (c, 2),
+                                _ => return Err(unsupported("unknown string escape")),
+                            };
+                            bytes.push(byte);
+                            self.pos += len;
+                        }
+                    }
+                }
+                Some(&b) => {
+                    bytes.push(b);
+                    self.pos += 1;
+                }
+            }
+        }
+        self.pos += 1;
+        Ok(Sexp::Str(bytes))
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, mut n: u32) {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_i32(out: &mut Vec<u8>, mut n: i32) {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7; // arithmetic shift, keeps the sign
+        let done = (n == 0 && byte & 0x40 == 0) || (n == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_u32(out, bytes.len() as u32);
+    out.extend_from_slice(bytes);
+}
+
+fn write_section(out: &mut Vec<u8>, id: u8, count: usize, content: &[u8]) {
+    if count == 0 {
+        return;
+    }
+    let mut body = Vec::new();
+    write_u32(&mut body, count as u32);
+    body.extend_from_slice(content);
+    out.push(id);
+    write_bytes(out, &body);
+}
+
+fn number<T: std::str::FromStr>(s: &Sexp) -> Result<T, CompileError> {
+    s.atom()
+        .and_then(|a| a.parse().ok())
+        .ok_or_else(|| unsupported("missing number"))
+}
+
+fn string(s: &Sexp) -> Result<&[u8], CompileError> {
+    match s {
+        Sexp::Str(bytes) => Ok(bytes),
+        _ => Err(unsupported("missing string")),
+    }
+}
+
+fn val_type(s: &Sexp) -> Result<u8, CompileError> {
+    match s.atom() {
+        Some("i32") => Ok(I32),
+        Some(other) => Err(unsupported(format_args!("value type `{}`", other))),
+        None => Err(unsupported("missing value type")),
+    }
+}
+
+// index of a function, local or label : `$name` or a number
+fn index(s: &Sexp, names: &HashMap<String, u32>) -> Result<u32, CompileError> {
+    match s.atom() {
+        Some(a) if a.starts_with('$') => names
+            .get(a)
+            .copied()
+            .ok_or_else(|| unsupported(format_args!("unknown name `{}`", a))),
+        _ => number(s),
+    }
+}
+
+// give a `$name` to an index, each name only once
+fn define(names: &mut HashMap<String, u32>, name: &str, idx: u32) -> Result<(), CompileError> {
+    if names.insert(name.to_string(), idx).is_some() {
+        return Err(unsupported(format_args!("duplicate name `{}`", name)));
+    }
+    Ok(())
+}
+
+// (params, results) of a function type
+type FuncType = (Vec<u8>, Vec<u8>);
+
+// a (func ...) of the module, split into its signature and its body
+struct Func<'a> {
+    ty: FuncType,
+    names: HashMap<String, u32>, // parameters and locals
+    locals: Vec<u8>,             // types of the locals after the parameters
+    body: &'a [Sexp],
+}
+
+// read the (param ...), (result ...) and (local ...) at the start of items,
+// return the signature and the position of the first instruction
+fn signature(items: &[Sexp]) -> Result<(Func<'_>, usize), CompileError> {
+    let mut func = Func {
+        ty: (Vec::new(), Vec::new()),
+        names: HashMap::new(),
+        locals: Vec::new(),
+        body: &[],
+    };
+    let mut next = 0;
+    for item in items {
+        let (target, rest) = if let Some(rest) = item.form("param") {
+            (0, rest)
+        } else if let Some(rest) = item.form("result") {
+            (1, rest)
+        } else if let Some(rest) = item.form("local") {
+            (2, rest)
+        } else {
+            break;
+        };
+        // a named entry `$x i32` declares one value, `i32 i32` several ones
+        let rest = match rest.first().and_then(Sexp::atom) {
+            Some(name) if name.starts_with('$') => {
+                let idx = (func.ty.0.len() + func.locals.len()) as u32;
+                define(&mut func.names, name, idx)?;
+                &rest[1..]
+            }
+            _ => rest,
+        };
+        for t in rest {
+            let t = val_type(t)?;
+            match target {
+                0 => func.ty.0.push(t),
+                1 => func.ty.1.push(t),
+                _ => func.locals.push(t),
+            }
+        }
+        next += 1;
+    }
+    Ok((func, next))
+}
+
+// the optional `$label` at the start of a folded block
+fn label(items: &[Sexp]) -> (Option<String>, &[Sexp]) {
+    match items.first().and_then(Sexp::atom) {
+        Some(l) if l.starts_with('$') => (Some(l.to_string()), &items[1..]),
+        _ => (None, items),
+    }
+}
+
+// instructions of a function body
+struct Body<'a> {
+    code: Vec<u8>,
+    funcs: &'a HashMap<String, u32>,
+    locals: &'a HashMap<String, u32>,
+    labels: Vec<Option<String>>, // enclosing blocks, innermost last
+}
+
+impl Body<'_> {
+    fn instrs(&mut self, items: &[Sexp]) -> Result<(), CompileError> {
+        let mut items = items.iter().peekable();
+        while let Some(item) = items.next() {
+            match item {
+                Sexp::Atom(op) => self.instr(op, &mut items)?,
+                Sexp::List(list) => self.folded(list)?,
+                Sexp::Str(_) => return Err(unsupported("string instead of an instruction")),
+            }
+        }
+        Ok(())
+    }
+
+    // (op immediates... operands...) : the operands are computed first
+    fn folded(&mut self, list: &[Sexp]) -> Result<(), CompileError> {
+        let op = list
+            .first()
+            .and_then(Sexp::atom)
+            .ok_or_else(|| unsupported("folded instruction without name"))?;
+        let rest = &list[1..];
+        match op {
+            "block" | "loop" => {
+                // (block $label? instructions...)
+                let (label, body) = label(rest);
+                self.code
+                    .extend([if op == "block" { 0x02 } else { 0x03 }, EMPTY_BLOCK]);
+                self.labels.push(label);
+                self.instrs(body)?;
+                self.code.push(0x0B);
+                self.labels.pop();
+            }
+            "if" => {
+                // (if $label? cond... (then ...) (else ...)?)
+                let (label, rest) = label(rest);
+                let mut then: &[Sexp] = &[];
+                let mut els = None;
+                for item in rest {
+                    if let Some(body) = item.form("then") {
+                        then = body;
+                    } else if let Some(body) = item.form("else") {
+                        els = Some(body);
+                    } else {
+                        self.instrs(std::slice::from_ref(item))?;
+                    }
+                }
+                self.code.extend([0x04, EMPTY_BLOCK]);
+                self.labels.push(label);
+                self.instrs(then)?;
+                if let Some(els) = els {
+                    self.code.push(0x05);
+                    self.instrs(els)?;
+                }
+                self.code.push(0x0B);
+                self.labels.pop();
+            }
+            _ => {
+                let count = rest
+                    .iter()
+                    .take_while(|s| matches!(s, Sexp::Atom(_)))
+                    .count();
+                let (immediates, operands) = rest.split_at(count);
+                self.instrs(operands)?;
+                self.instr(op, &mut immediates.iter().peekable())?;
+            }
+        }
+        Ok(())
+    }
+
+    // one instruction, taking its immediates from the next items
+    fn instr<'s>(
+        &mut self,
+        op: &str,
+        items: &mut std::iter::Peekable<impl Iterator<Item = &'s Sexp>>,
+    ) -> Result<(), CompileError> {
+        if let Some(&(_, code)) = PLAIN_OPS.iter().find(|(name, _)| *name == op) {
+            self.code.push(code);
+            return Ok(());
+        }
+        if let Some(&(_, code, align)) = MEMORY_OPS.iter().find(|(name, ..)| *name == op) {
+            self.code.push(code);
+            write_u32(&mut self.code, align);
+            write_u32(&mut self.code, 0); // offset
+            return Ok(());
+        }
+        let mut immediate = || {
+            items
+                .next()
+                .ok_or_else(|| unsupported(format_args!("`{}` without operand", op)))
+        };
+        match op {
+            "block" | "loop" | "if" => {
+                let code = match op {
+                    "block" => 0x02,
+                    "loop" => 0x03,
+                    _ => 0x04,
+                };
+                let label = match items.peek().and_then(|s| s.atom()) {
+                    Some(l) if l.starts_with('$') => {
+                        items.next();
+                        Some(l.to_string())
+                    }
+                    _ => None,
+                };
+                self.code.extend([code, EMPTY_BLOCK]);
+                self.labels.push(label);
+            }
+            "else" => self.code.push(0x05),
+            "end" => {
+                self.code.push(0x0B);
+                self.labels.pop();
+            }
+            "br" | "br_if" => {
+                let target = immediate()?;
+                let depth = match target.atom() {
+                    Some(l) if l.starts_with('$') => self
+                        .labels
+                        .iter()
+                        .rev()
+                        .position(|label| label.as_deref() == Some(l))
+                        .ok_or_else(|| unsupported(format_args!("unknown label `{}`", l)))?
+                        as u32,
+                    _ => number(target)?,
+                };
+                self.code.push(if op == "br" { 0x0C } else { 0x0D });
+                write_u32(&mut self.code, depth);
+            }
+            "i32.const" => {
+                let n = number(immediate()?)?;
+                self.code.push(0x41);
+                write_i32(&mut self.code, n);
+            }
+            "local.get" | "local.set" | "local.tee" => {
+                let idx = index(immediate()?, self.locals)?;
+                self.code.push(match op {
+                    "local.get" => 0x20,
+                    "local.set" => 0x21,
+                    _ => 0x22,
+                });
+                write_u32(&mut self.code, idx);
+            }
+            "call" => {
+                let idx = index(immediate()?, self.funcs)?;
+                self.code.push(0x10);
+                write_u32(&mut self.code, idx);
+            }
+            _ => return Err(unsupported(format_args!("instruction `{}`", op))),
+        }
+        Ok(())
+    }
+}
+
+// ($name, (export "name")..., remaining items) at the start of a field
+type Header<'a> = (Option<&'a str>, Vec<&'a [u8]>, &'a [Sexp]);
+
+fn name_and_exports(mut items: &[Sexp]) -> Result<Header<'_>, CompileError> {
+    let name = match items.first().and_then(Sexp::atom) {
+        Some(n) if n.starts_with('$') => {
+            items = &items[1..];
+            Some(n)
+        }
+        _ => None,
+    };
+    let mut exports = Vec::new();
+    while let Some(export) = items.first().and_then(|s| s.form("export")) {
+        exports.push(string(
+            export.first().ok_or_else(|| unsupported("empty export"))?,
+        )?);
+        items = &items[1..];
+    }
+    Ok((name, exports, items))
+}
+
+// Assemble the WAT text of a module, as written by codegen::emit_module
+pub fn assemble(wat: &str) -> Result<Vec<u8>, CompileError> {
+    let mut reader = Reader {
+        src: wat.as_bytes(),
+        pos: 0,
+    };
+    reader.skip_blank();
+    if reader.pos == reader.src.len() {
+        return Err(unsupported("empty module"));
+    }
+    let module = reader.sexp()?;
+    let fields = module
+        .form("module")
+        .ok_or_else(|| unsupported("text without `(module`"))?;
+
+    // read the fields : the imported functions take the first indices, then
+    // come the defined ones. The bodies are assembled once every function
+    // name is known.
+    let mut imports: Vec<(&[u8], &[u8], FuncType)> = Vec::new();
+    let mut funcs: Vec<Func> = Vec::new();
+    let mut func_names = HashMap::new();
+    let mut memory = Vec::new();
+    let mut memories = 0;
+    let mut exports: Vec<(&[u8], u8, &Sexp)> = Vec::new(); // (name, kind, `$name` or index)
+    let mut func_exports: Vec<(&[u8], u32)> = Vec::new(); // inline exports
+    let mut data = Vec::new();
+    let mut segments = 0;
+    let memory_zero = Sexp::Atom("0".to_string());
+
+    for field in fields {
+        if let Some(items) = field.form("import") {
+            // (import "module" "name" (func $name? (param ...) (result ...)))
+            let [module, name, desc] = items else {
+                return Err(unsupported("malformed import"));
+            };
+            let func = desc
+                .form("func")
+                .ok_or_else(|| unsupported("import of something else than a function"))?;
+            let (func_name, _, sig) = name_and_exports(func)?;
+            let idx = imports.len() as u32;
+            if let Some(n) = func_name {
+                define(&mut func_names, n, idx)?;
+            }
+            imports.push((string(module)?, string(name)?, signature(sig)?.0.ty));
+        } else if let Some(items) = field.form("func") {
+            let (name, inline, items) = name_and_exports(items)?;
+            let idx = (imports.len() + funcs.len()) as u32;
+            if let Some(n) = name {
+                define(&mut func_names, n, idx)?;
+            }
+            func_exports.extend(inline.into_iter().map(|e| (e, idx)));
+            let (mut func, next) = signature(items)?;
+            func.body = &items[next..];
+            funcs.push(func);
+        } else if let Some(items) = field.form("memory") {
+            // (memory (export "name")? min max?)
+            let (_, inline, items) = name_and_exports(items)?;
+            exports.extend(inline.into_iter().map(|e| (e, EXPORT_MEMORY, &memory_zero)));
+            match items {
+                [min] => {
+                    memory.push(0x00);
+                    write_u32(&mut memory, number(min)?);
+                }
+                [min, max] => {
+                    memory.push(0x01);
+                    write_u32(&mut memory, number(min)?);
+                    write_u32(&mut memory, number(max)?);
+                }
+                _ => return Err(unsupported("malformed memory")),
+            }
+            memories += 1;
+        } else if let Some(items) = field.form("data") {
+            // (data (i32.const offset) "bytes"...)
+            let offset = items
+                .first()
+                .and_then(|s| s.form("i32.const"))
+                .and_then(|s| s.first())
+                .ok_or_else(|| unsupported("data without offset"))?;
+            let mut bytes = Vec::new();
+            for s in &items[1..] {
+                bytes.extend_from_slice(string(s)?);
+            }
+            data.push(0x00); // active segment of memory 0
+            data.push(0x41);
+            write_i32(&mut data, number(offset)?);
+            data.push(0x0B);
+            write_bytes(&mut data, &bytes);
+            segments += 1;
+        } else if let Some(items) = field.form("export") {
+            // (export "name" (func $f)) or (export "name" (memory 0))
+            let [name, desc] = items else {
+                return Err(unsupported("malformed export"));
+            };
+            let (kind, target) = if let Some([target]) = desc.form("func") {
+                (EXPORT_FUNC, target)
+            } else if let Some([target]) = desc.form("memory") {
+                (EXPORT_MEMORY, target)
+            } else {
+                return Err(unsupported("malformed export"));
+            };
+            exports.push((string(name)?, kind, target));
+        } else {
+            return Err(unsupported("module field"));
+        }
+    }
+
+    // one type per distinct signature
+    let mut types: Vec<&FuncType> = Vec::new();
+    let mut type_index = |ty| match types.iter().position(|t| *t == ty) {
+        Some(i) => i as u32,
+        None => {
+            types.push(ty);
+            types.len() as u32 - 1
+        }
+    };
+
+    let mut import_sec = Vec::new();
+    for (module, name, ty) in &imports {
+        write_bytes(&mut import_sec, module);
+        write_bytes(&mut import_sec, name);
+        import_sec.push(0x00); // function
+        write_u32(&mut import_sec, type_index(ty));
+    }
+
+    let mut function_sec = Vec::new();
+    let mut code_sec = Vec::new();
+    for func in &funcs {
+        write_u32(&mut function_sec, type_index(&func.ty));
+        // locals are declared as runs of the same type
+        let mut runs: Vec<(u32, u8)> = Vec::new();
+        for &t in &func.locals {
+            match runs.last_mut() {
+                Some((count, last)) if *last == t => *count += 1,
+                _ => runs.push((1, t)),
+            }
+        }
+        let mut body = Body {
+            code: Vec::new(),
+            funcs: &func_names,
+            locals: &func.names,
+            labels: Vec::new(),
+        };
+        write_u32(&mut body.code, runs.len() as u32);
+        for (count, t) in runs {
+            write_u32(&mut body.code, count);
+            body.code.push(t);
+        }
+        body.instrs(func.body)?;
+        body.code.push(0x0B);
+        write_bytes(&mut code_sec, &body.code);
+    }
+
+    let mut export_sec = Vec::new();
+    for (name, idx) in &func_exports {
+        write_bytes(&mut export_sec, name);
+        export_sec.push(EXPORT_FUNC);
+        write_u32(&mut export_sec, *idx);
+    }
+    for (name, kind, target) in &exports {
+        let idx = if *kind == EXPORT_FUNC {
+            index(target, &func_names)?
+        } else {
+            number(target)?
+        };
+        write_bytes(&mut export_sec, name);
+        export_sec.push(*kind);
+        write_u32(&mut export_sec, idx);
+    }
+
+    let mut type_sec = Vec::new();
+    for (params, results) in &types {
+        type_sec.push(FUNC_TYPE);
+        write_bytes(&mut type_sec, params);
+        write_bytes(&mut type_sec, results);
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(VERSION);
+    write_section(&mut out, SEC_TYPE, types.len(), &type_sec);
+    write_section(&mut out, SEC_IMPORT, imports.len(), &import_sec);
+    write_section(&mut out, SEC_FUNCTION, funcs.len(), &function_sec);
+    write_section(&mut out, SEC_MEMORY, memories, &memory);
+    write_section(
+        &mut out,
+        SEC_EXPORT,
+        func_exports.len() + exports.len(),
+        &export_sec,
+    );
+    write_section(&mut out, SEC_CODE, funcs.len(), &code_sec);
+    write_section(&mut out, SEC_DATA, segments, &data);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Options, codegen, lexer::Lexer, parser::Parser};
+
+    fn leb_u32(n: u32) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_u32(&mut out, n);
+        out
+    }
+
+    fn leb_i32(n: i32) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_i32(&mut out, n);
+        out
+    }
+
+    #[test]
+    fn leb128_encoding() {
+        assert_eq!(leb_u32(0), [0x00]);
+        assert_eq!(leb_u32(127), [0x7F]);
+        assert_eq!(leb_u32(128), [0x80, 0x01]);
+        assert_eq!(leb_u32(624485), [0xE5, 0x8E, 0x26]);
+        // the sign bit of the last byte must match the sign of the value
+        assert_eq!(leb_i32(63), [0x3F]);
+        assert_eq!(leb_i32(64), [0xC0, 0x00]);
+        assert_eq!(leb_i32(-1), [0x7F]);
+        assert_eq!(leb_i32(-64), [0x40]);
+        assert_eq!(leb_i32(-65), [0xBF, 0x7F]);
+        assert_eq!(leb_i32(i32::MIN), [0x80, 0x80, 0x80, 0x80, 0x78]);
+    }
+
+    #[test]
+    fn module_with_one_function() {
+        let wat = "(module\n  ;; f(x) = x + 1\n  \
+                   (func $f (export \"f\") (param $x i32) (result i32)\n    \
+                   (i32.add (local.get $x) (i32.const 1))))";
+        let mut expected = b"\0asm\x01\0\0\0".to_vec();
+        expected.extend([0x01, 0x06, 0x01, 0x60, 0x01, 0x7F, 0x01, 0x7F]); // type
+        expected.extend([0x03, 0x02, 0x01, 0x00]); // function
+        expected.extend([0x07, 0x05, 0x01, 0x01, b'f', 0x00, 0x00]); // export
+        expected.extend([
+            0x0A, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x41, 0x01, 0x6A, 0x0B,
+        ]);
+        assert_eq!(assemble(wat).unwrap(), expected);
+    }
+
+    #[test]
+    fn generated_modules_are_assembled() {
+        let src = "fn main() {\n  let n = 3;\n  while n {\n    if n == 2 {\n      \
+                   log(\"two\");\n    } else {\n      log(n * 10);\n    }\n    n = n - 1;\n  }\n}";
+        let (_, prog, _) = Parser::new(Lexer::new(src))
+            .unwrap()
+            .parse_main_program()
+            .unwrap();
+        let wasm =
+            assemble(&codegen::emit_module(&prog, &[], &Options::default()).unwrap()).unwrap();
+        assert!(wasm.starts_with(b"\0asm\x01\0\0\0"));
+        // the string of the data section is copied as is
+        assert!(wasm.windows(3).any(|w| w == b"two"));
+    }
+
+    #[test]
+    fn unknown_text_is_unsupported() {
+        for wat in [
+            "",
+            "(func)",
+            "(module (func f32.add))",
+            "(module (table 1 funcref))",
+            // a name given twice
+            "(module (func $a) (func $a))",
+            "(module (func (param $x i32) (param $x i32)))",
+        ] {
+            let e = assemble(wat).unwrap_err();
+            assert!(
+                matches!(e, CompileError::Unsupported { .. }),
+                "{:?}: {}",
+                wat,
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,488 @@
+// A small interpreter for the WAT modules emitted by codegen, so that tests
+// can run the generated code without a wasm runtime. Only the instructions
+// codegen uses are known, and only i32 values.
+
+use std::collections::HashMap;
+
+// s-expression of the WAT text
+#[derive(Debug)]
+enum Sexp {
+    Atom(String),
+    Str(Vec<u8>),
+    List(Vec<Sexp>),
+}
+
+fn parse_sexps(src: &str) -> Vec<Sexp> {
+    let bytes = src.as_bytes();
+    let mut stack: Vec<Vec<Sexp>> = vec![Vec::new()];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'(' => {
+                stack.push(Vec::new());
+                i += 1;
+            }
+            b')' => {
+                let list = stack.pop().expect("unbalanced )");
+                stack
+                    .last_mut()
+                    .expect("unbalanced )")
+                    .push(Sexp::List(list));
+                i += 1;
+            }
+            b'"' => {
+                // string with \xx escapes
+                let mut s = Vec::new();
+                i += 1;
+                while bytes[i] != b'"' {
+                    if bytes[i] == b'\\' {
+                        let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap();
+                        s.push(u8::from_str_radix(hex, 16).expect("bad string escape"));
+                        i += 3;
+                    } else {
+                        s.push(bytes[i]);
+                        i += 1;
+                    }
+                }
+                i += 1;
+                stack.last_mut().unwrap().push(Sexp::Str(s));
+            }
+            b if b.is_ascii_whitespace() => i += 1,
+            b';' if bytes.get(i + 1) == Some(&b';') => {
+                // line comment
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            _ => {
+                let start = i;
+                while i < bytes.len() && !b"() \t\n\"".contains(&bytes[i]) {
+                    i += 1;
+                }
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .push(Sexp::Atom(src[start..i].to_string()));
+            }
+        }
+    }
+    assert_eq!(stack.len(), 1, "unbalanced (");
+    stack.pop().unwrap()
+}
+
+fn atom(s: &Sexp) -> Option<&str> {
+    match s {
+        Sexp::Atom(a) => Some(a),
+        _ => None,
+    }
+}
+
+// the head atom of a list : `func` for (func ...)
+fn head(s: &Sexp) -> Option<&str> {
+    match s {
+        Sexp::List(items) => items.first().and_then(atom),
+        _ => None,
+    }
+}
+
+// flat instruction, the folded form is unfolded
+#[derive(Debug, Clone)]
+enum Instr {
+    Op(String),                  // i32.add, drop, ...
+    Imm(String, String),         // i32.const 1, local.get $x, call $f, br 0, ...
+    Block(bool, Option<String>), // block or loop (true), with its label
+    If(Option<String>),
+    Else,
+    End,
+}
+
+fn has_immediate(op: &str) -> bool {
+    matches!(
+        op,
+        "i32.const" | "local.get" | "local.set" | "local.tee" | "call" | "br" | "br_if"
+    )
+}
+
+// label of a block: `loop $digits`
+fn label(items: &[Sexp], i: &mut usize) -> Option<String> {
+    let l = items
+        .get(*i)
+        .and_then(atom)
+        .filter(|a| a.starts_with('$'))?;
+    *i += 1;
+    Some(l.to_string())
+}
+
+// unfold a sequence of instructions, plain or folded
+fn flatten(items: &[Sexp], out: &mut Vec<Instr>) {
+    let mut i = 0;
+    while i < items.len() {
+        match &items[i] {
+            Sexp::Atom(op) => {
+                i += 1;
+                match op.as_str() {
+                    "block" | "loop" => {
+                        let l = label(items, &mut i);
+                        out.push(Instr::Block(op == "loop", l));
+                    }
+                    "if" => {
+                        let l = label(items, &mut i);
+                        out.push(Instr::If(l));
+                    }
+                    "else" => out.push(Instr::Else),
+                    "end" => out.push(Instr::End),
+                    _ if has_immediate(op) => {
+                        let imm = atom(&items[i]).expect("missing immediate").to_string();
+                        i += 1;
+                        out.push(Instr::Imm(op.clone(), imm));
+                    }
+                    _ => out.push(Instr::Op(op.clone())),
+                }
+            }
+            Sexp::List(list) => {
+                flatten_folded(list, out);
+                i += 1;
+            }
+            Sexp::Str(_) => panic!("unexpected string in code"),
+        }
+    }
+}
+
+// (op args...) : the arguments are pushed before the operation
+fn flatten_folded(list: &[Sexp], out: &mut Vec<Instr>) {
+    let op = atom(&list[0]).expect("folded instruction without name");
+    let mut i = 1;
+    match op {
+        "block" | "loop" => {
+            let l = label(list, &mut i);
+            out.push(Instr::Block(op == "loop", l));
+            flatten(&list[i..], out);
+            out.push(Instr::End);
+        }
+        "if" => {
+            let l = label(list, &mut i);
+            let mut arms = Vec::new();
+            for item in &list[i..] {
+                match head(item) {
+                    Some("then") | Some("else") => arms.push(item),
+                    _ => flatten(std::slice::from_ref(item), out), // the condition
+                }
+            }
+            out.push(Instr::If(l));
+            for arm in arms {
+                let Sexp::List(arm) = arm else { unreachable!() };
+                if atom(&arm[0]) == Some("else") {
+                    out.push(Instr::Else);
+                }
+                flatten(&arm[1..], out);
+            }
+            out.push(Instr::End);
+        }
+        _ => {
+            let imm = if has_immediate(op) {
+                i += 1;
+                Some(atom(&list[1]).expect("missing immediate").to_string())
+            } else {
+                None
+            };
+            flatten(&list[i..], out);
+            out.push(match imm {
+                Some(imm) => Instr::Imm(op.to_string(), imm),
+                None => Instr::Op(op.to_string()),
+            });
+        }
+    }
+}
+
+struct Func {
+    locals: Vec<String>, // parameters first
+    params: usize,
+    code: Vec<Instr>,
+    ends: HashMap<usize, (Option<usize>, usize)>, // block start -> (else, end)
+}
+
+// match each block, loop and if with its else and end
+fn match_blocks(code: &[Instr]) -> HashMap<usize, (Option<usize>, usize)> {
+    let mut ends = HashMap::new();
+    let mut open: Vec<(usize, Option<usize>)> = Vec::new();
+    for (pc, instr) in code.iter().enumerate() {
+        match instr {
+            Instr::Block(..) | Instr::If(_) => open.push((pc, None)),
+            Instr::Else => open.last_mut().expect("else outside of if").1 = Some(pc),
+            Instr::End => {
+                let (start, els) = open.pop().expect("end outside of a block");
+                ends.insert(start, (els, pc));
+            }
+            _ => {}
+        }
+    }
+    assert!(open.is_empty(), "block without end");
+    ends
+}
+
+fn parse_func(items: &[Sexp]) -> (String, Func) {
+    let name = atom(&items[1]).expect("anonymous function").to_string();
+    let mut locals = Vec::new();
+    let mut params = 0;
+    let mut i = 2;
+    while let Some(h) = items.get(i).and_then(head) {
+        let Sexp::List(decl) = &items[i] else {
+            unreachable!()
+        };
+        match h {
+            "param" | "local" => {
+                for d in &decl[1..] {
+                    let d = atom(d).unwrap();
+                    if d.starts_with('$') {
+                        locals.push(d.to_string());
+                    }
+                }
+                if h == "param" {
+                    params = locals.len();
+                }
+            }
+            "result" => {}
+            _ => break, // a folded instruction
+        }
+        i += 1;
+    }
+    let mut code = Vec::new();
+    flatten(&items[i..], &mut code);
+    let ends = match_blocks(&code);
+    (
+        name,
+        Func {
+            locals,
+            params,
+            code,
+            ends,
+        },
+    )
+}
+
+pub struct Vm {
+    funcs: HashMap<String, Func>,
+    pub memory: Vec<u8>,
+    pub stdout: Vec<u8>,
+}
+
+// open block during the execution
+struct Frame {
+    is_loop: bool,
+    label: Option<String>,
+    start: usize,
+    end: usize,
+    height: usize, // stack height at the start of the block
+}
+
+impl Vm {
+    // load a module: its functions, memory and data segments
+    pub fn new(wat: &str) -> Self {
+        let module = parse_sexps(wat);
+        let Some(Sexp::List(items)) = module.first() else {
+            panic!("no module");
+        };
+        let mut vm = Self {
+            funcs: HashMap::new(),
+            memory: Vec::new(),
+            stdout: Vec::new(),
+        };
+        let mut data = Vec::new();
+        for item in &items[1..] {
+            let Sexp::List(list) = item else { continue };
+            match head(item) {
+                Some("func") => {
+                    let (name, f) = parse_func(list);
+                    vm.funcs.insert(name, f);
+                }
+                Some("memory") => {
+                    let pages: usize = list.iter().rev().find_map(atom).unwrap().parse().unwrap();
+                    vm.memory = vec![0; pages * 65536];
+                }
+                Some("data") => {
+                    let Sexp::List(offset) = &list[1] else {
+                        panic!("data without offset")
+                    };
+                    let offset: usize = atom(&offset[1]).unwrap().parse().unwrap();
+                    let Sexp::Str(s) = &list[2] else {
+                        panic!("data without string")
+                    };
+                    data.push((offset, s.clone()));
+                }
+                _ => {} // imports and exports
+            }
+        }
+        for (offset, s) in data {
+            vm.memory[offset..offset + s.len()].copy_from_slice(&s);
+        }
+        vm
+    }
+
+    // the output of the function `main`, as text
+    pub fn run_main(wat: &str) -> String {
+        let mut vm = Self::new(wat);
+        vm.call("$u_main", &[]);
+        String::from_utf8(vm.stdout).unwrap()
+    }
+
+    fn load(&self, addr: i32) -> i32 {
+        let a = addr as usize;
+        i32::from_le_bytes(self.memory[a..a + 4].try_into().unwrap())
+    }
+
+    // fd_write(fd, iovs, iovs_len, nwritten) of WASI
+    fn fd_write(&mut self, args: &[i32]) -> i32 {
+        let mut written = 0;
+        for k in 0..args[2] {
+            let ptr = self.load(args[1] + 8 * k) as usize;
+            let len = self.load(args[1] + 8 * k + 4) as usize;
+            let bytes = self.memory[ptr..ptr + len].to_vec();
+            self.stdout.extend(bytes);
+            written += len as i32;
+        }
+        let n = args[3] as usize;
+        self.memory[n..n + 4].copy_from_slice(&written.to_le_bytes());
+        0
+    }
+
+    // call a function and return the values left on its stack
+    pub fn call(&mut self, name: &str, args: &[i32]) -> Vec<i32> {
+        if name == "$fd_write" {
+            return vec![self.fd_write(args)];
+        }
+        let f = &self.funcs[name];
+        let (code, ends, params) = (f.code.clone(), f.ends.clone(), f.params);
+        let names = f.locals.clone();
+        assert_eq!(args.len(), params, "wrong number of arguments to {}", name);
+        let mut locals = args.to_vec();
+        locals.resize(names.len(), 0);
+        let slot = |x: &str| {
+            names
+                .iter()
+                .position(|n| n == x)
+                .or_else(|| x.parse().ok())
+                .expect("unknown local")
+        };
+        let mut stack: Vec<i32> = Vec::new();
+        let mut frames: Vec<Frame> = Vec::new();
+        let mut pc = 0;
+        while pc < code.len() {
+            let mut next = pc + 1;
+            match &code[pc] {
+                Instr::Block(is_loop, label) => frames.push(Frame {
+                    is_loop: *is_loop,
+                    label: label.clone(),
+                    start: pc,
+                    end: ends[&pc].1,
+                    height: stack.len(),
+                }),
+                Instr::If(label) => {
+                    let (els, end) = ends[&pc];
+                    let cond = stack.pop().unwrap();
+                    frames.push(Frame {
+                        is_loop: false,
+                        label: label.clone(),
+                        start: pc,
+                        end,
+                        height: stack.len(),
+                    });
+                    if cond == 0 {
+                        next = els.map_or(end, |e| e + 1);
+                    }
+                }
+                // end of the then arm: skip the else arm
+                Instr::Else => next = frames.last().unwrap().end,
+                Instr::End => {
+                    frames.pop();
+                }
+                Instr::Imm(op, imm) => match op.as_str() {
+                    "i32.const" => stack.push(imm.parse().unwrap()),
+                    "local.get" => stack.push(locals[slot(imm)]),
+                    "local.set" => locals[slot(imm)] = stack.pop().unwrap(),
+                    "local.tee" => locals[slot(imm)] = *stack.last().unwrap(),
+                    "call" => {
+                        let func_params = match self.funcs.get(imm.as_str()) {
+                            Some(f) => f.params,
+                            None => 4, // fd_write
+                        };
+                        let args = stack.split_off(stack.len() - func_params);
+                        let results = self.call(imm, &args);
+                        stack.extend(results);
+                    }
+                    "br" | "br_if" => {
+                        if op == "br" || stack.pop().unwrap() != 0 {
+                            let depth = match imm.parse::<usize>() {
+                                Ok(d) => d,
+                                Err(_) => frames
+                                    .iter()
+                                    .rev()
+                                    .position(|f| f.label.as_deref() == Some(imm.as_str()))
+                                    .expect("unknown label"),
+                            };
+                            let target = frames.len() - 1 - depth;
+                            stack.truncate(frames[target].height);
+                            if frames[target].is_loop {
+                                next = frames[target].start + 1;
+                                frames.truncate(target + 1);
+                            } else {
+                                next = frames[target].end + 1;
+                                frames.truncate(target);
+                            }
+                        }
+                    }
+                    _ => panic!("unknown instruction {}", op),
+                },
+                Instr::Op(op) => {
+                    if op == "return" {
+                        break;
+                    }
+                    self.op(op, &mut stack);
+                }
+            }
+            pc = next;
+        }
+        stack
+    }
+
+    // instruction without immediate
+    fn op(&mut self, op: &str, stack: &mut Vec<i32>) {
+        if op == "drop" {
+            stack.pop().unwrap();
+            return;
+        }
+        if op == "i32.eqz" {
+            let a = stack.pop().unwrap();
+            stack.push((a == 0) as i32);
+            return;
+        }
+        let b = stack.pop().unwrap();
+        let a = stack.pop().unwrap();
+        let v = match op {
+            "i32.add" => a.wrapping_add(b),
+            "i32.sub" => a.wrapping_sub(b),
+            "i32.mul" => a.wrapping_mul(b),
+            "i32.div_s" => a.checked_div(b).expect("trap: integer division"),
+            "i32.rem_s" => a.checked_rem(b).expect("trap: integer remainder"),
+            "i32.and" => a & b,
+            "i32.or" => a | b,
+            "i32.xor" => a ^ b,
+            "i32.eq" => (a == b) as i32,
+            "i32.ne" => (a != b) as i32,
+            "i32.lt_s" => (a < b) as i32,
+            "i32.le_s" => (a <= b) as i32,
+            "i32.gt_s" => (a > b) as i32,
+            "i32.ge_s" => (a >= b) as i32,
+            "i32.store" => {
+                let a = a as usize;
+                self.memory[a..a + 4].copy_from_slice(&b.to_le_bytes());
+                return;
+            }
+            "i32.store8" => {
+                self.memory[a as usize] = b as u8;
+                return;
+            }
+            _ => panic!("unknown instruction {}", op),
+        };
+        stack.push(v);
+    }
+}
@@ -0,0 +1,176 @@
+mod common;
+
+use common::Project;
+use std::{
+    fs,
+    process::{Command, Output},
+};
+
+fn gaufre(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gaufre")).args(args).output().unwrap()
+}
+
+#[test]
+fn conditional_import_is_loaded_only_when_defined() {
+    // debug.gfr does not exist: the build fails only when it is imported
+    let p = Project::new(
+        "cond",
+        &[("main.gfr", "import \"debug.gfr\" if DEBUG\nfn main() {\n  log(\"a\");\n}\n")],
+    );
+    let root = p.path("main.gfr");
+    let root = root.to_str().unwrap();
+    assert!(gaufre(&[root]).status.success());
+    assert!(!gaufre(&["--define", "DEBUG", root]).status.success());
+    assert!(!gaufre(&["--define", "DEBUG=1", root]).status.success());
+    assert!(gaufre(&["--define", "RELEASE", root]).status.success());
+}
+
+#[test]
+fn bundle_compiles_like_the_original_program() {
+    let p = Project::new("bundle", &[]);
+    let path = |rel| p.path(rel).to_string_lossy().into_owned();
+    let (wat, bundle, bundle_wat) = (path("a.wat"), path("b.gfr"), path("b.wat"));
+    let no_comments = "--no-debug-comments";
+    assert!(gaufre(&[no_comments, "exemple/hello.gfr", &wat]).status.success());
+    assert!(gaufre(&["bundle", "exemple/hello.gfr", "-o", &bundle]).status.success());
+    let src = fs::read_to_string(&bundle).unwrap();
+    assert!(!src.contains("import"), "{}", src);
+    assert!(gaufre(&[no_comments, &bundle, &bundle_wat]).status.success());
+    assert_eq!(fs::read_to_string(wat).unwrap(), fs::read_to_string(bundle_wat).unwrap());
+}
+
+#[test]
+fn help_and_version() {
+    for flag in ["--help", "-h"] {
+        let out = gaufre(&[flag]);
+        assert!(out.status.success());
+        assert!(String::from_utf8_lossy(&out.stdout).starts_with("usage: gaufre"));
+    }
+    let out = gaufre(&["--version"]);
+    assert!(out.status.success());
+    let version = format!("gaufre {}\n", env!("CARGO_PKG_VERSION"));
+    assert_eq!(String::from_utf8_lossy(&out.stdout), version);
+}
+
+#[test]
+fn usage_error_exits_with_2() {
+    let out = gaufre(&["--frobnicate", "exemple/hello.gfr"]);
+    assert_eq!(out.status.code(), Some(2));
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.starts_with("error: unknown option --frobnicate\n\nusage:"), "{}", err);
+    assert_eq!(gaufre(&[]).status.code(), Some(2));
+}
+
+#[test]
+fn emit_wasm_writes_a_binary_module() {
+    let p = Project::new("emit", &[("main.gfr", "fn main() {\n  log(\"a\");\n}\n")]);
+    let root = p.path("main.gfr");
+    assert!(gaufre(&["--emit", "wasm", root.to_str().unwrap()]).status.success());
+    let wasm = fs::read(p.path("main.wasm")).unwrap();
+    assert!(wasm.starts_with(b"\0asm\x01\0\0\0"), "{:?}", wasm);
+    assert!(!p.path("main.wat").exists());
+}
+
+#[test]
+fn debug_comments_can_be_turned_off() {
+    let p = Project::new("comments", &[("main.gfr", "fn main() {\n  log(\"a\");\n}\n")]);
+    let root = p.path("main.gfr");
+    let root = root.to_str().unwrap();
+    assert!(gaufre(&[root]).status.success());
+    let wat = fs::read_to_string(p.path("main.wat")).unwrap();
+    assert!(wat.contains(&format!("    ;; {}:2:3\n", root)), "{}", wat);
+    assert!(gaufre(&["--no-debug-comments", root]).status.success());
+    let wat = fs::read_to_string(p.path("main.wat")).unwrap();
+    assert!(!wat.contains(";;"), "{}", wat);
+}
+
+#[test]
+fn dump_ast_prints_the_program_and_its_functions() {
+    let p = Project::new(
+        "dump-ast",
+        &[
+            ("main.gfr", "import \"a.gfr\"\nfn main() {\n  call a(7);\n}\n"),
+            ("a.gfr", "fn a(n) {\n  log(n);\n}\n"),
+        ],
+    );
+    let root = p.path("main.gfr");
+    let out = gaufre(&["--dump-ast", root.to_str().unwrap()]);
+    assert!(out.status.success());
+    let ast = String::from_utf8_lossy(&out.stdout);
+    assert!(ast.starts_with("Program {\n"), "{}", ast);
+    assert!(ast.contains("Call {\n") && ast.contains("name: \"a\",\n"), "{}", ast);
+    assert!(ast.contains("Function {\n") && ast.contains("Log {\n"), "{}", ast);
+    // nothing is compiled
+    assert!(!p.path("main.wat").exists());
+}
+
+#[test]
+fn dump_tokens_prints_one_token_per_line() {
+    let p = Project::new(
+        "dump-tokens",
+        &[("main.gfr", "fn main() {}\n"), ("bad.gfr", "log(1 # 2)")],
+    );
+    let root = p.path("main.gfr");
+    let root = root.to_str().unwrap();
+    let out = gaufre(&["--dump-tokens", root]);
+    assert!(out.status.success());
+    let lines: Vec<String> = String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .map(|l| l.replace(root, "main.gfr"))
+        .collect();
+    let expected = [
+        "main.gfr:1:1 Fn",
+        "main.gfr:1:4 Main",
+        "main.gfr:1:8 LParen",
+        "main.gfr:1:9 RParen",
+        "main.gfr:1:11 LBrace",
+        "main.gfr:1:12 RBrace",
+        "main.gfr:2:1 Eof",
+    ];
+    assert_eq!(lines, expected);
+    assert!(!p.path("main.wat").exists());
+    // the tokens before a lex error are printed, then the error
+    let bad = p.path("bad.gfr");
+    let out = gaufre(&["--dump-tokens", bad.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&out.stdout).lines().count(), 3);
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.ends_with(":1:7: error[E0004]: unexpected character: 0x23\n"), "{}", err);
+}
+
+#[test]
+fn errors_are_printed_as_diagnostics() {
+    // no Debug output, one line naming the file
+    let out = gaufre(&["exemple/none.gfr"]);
+    assert_eq!(out.status.code(), Some(1));
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.starts_with("exemple/none.gfr: error[E0021]: "), "{}", err);
+    assert_eq!(err.lines().count(), 1, "{}", err);
+    // the output file is named when it cannot be written
+    let out = gaufre(&["exemple/hello.gfr", "exemple/none/hello.wat"]);
+    assert_eq!(out.status.code(), Some(1));
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.starts_with("exemple/none/hello.wat: error[E0021]: "), "{}", err);
+    // an unknown code is a usage error
+    let out = gaufre(&["--explain", "E9999"]);
+    assert_eq!(out.status.code(), Some(2));
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.starts_with("error: unknown error code E9999\n\nusage:"), "{}", err);
+}
+
+#[test]
+fn fail_fast_false_reports_every_semantic_error() {
+    let src = "fn main() {\n  call g();\n  log(x);\n  return 1;\n}\n";
+    let p = Project::new("fail-fast", &[("main.gfr", src)]);
+    let root = p.path("main.gfr");
+    let root = root.to_str().unwrap();
+    let out = gaufre(&[root]);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&out.stderr).lines().count(), 1);
+    let out = gaufre(&["--fail-fast=false", root]);
+    assert_eq!(out.status.code(), Some(1));
+    let err = String::from_utf8_lossy(&out.stderr);
+    let codes: Vec<_> = err.lines().map(|l| l.split(['[', ']']).nth(1).unwrap()).collect();
+    assert_eq!(codes, ["E0007", "E0015", "E0019"], "{}", err);
+    assert!(err.lines().all(|l| l.starts_with(root)), "{}", err);
+}
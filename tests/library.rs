@@ -0,0 +1,100 @@
+mod common;
+
+use common::Project;
+use gaufre::{Options, error::CompileError, parser::ParseError};
+use std::{fs, path::Path};
+
+#[test]
+fn compile_the_example_program() {
+    let wat = gaufre::compile(Path::new("exemple/hello.gfr")).unwrap();
+    assert_eq!(wat, fs::read_to_string("exemple/hello.wat").unwrap());
+}
+
+#[test]
+fn bundle_compiles_like_the_original_program() {
+    let root = Path::new("exemple/hello.gfr");
+    let src = gaufre::bundle(root, &Options::default()).unwrap();
+    assert!(!src.contains("import"), "{}", src);
+    let p = Project::new("lib-bundle", &[("bundle.gfr", &src)]);
+    // the positions of the comments are the ones of the bundle
+    let options = Options {
+        debug_comments: false,
+        ..Options::default()
+    };
+    let bundled = gaufre::compile_with(&p.path("bundle.gfr"), &options).unwrap();
+    assert_eq!(bundled, gaufre::compile_with(root, &options).unwrap());
+}
+
+#[test]
+fn missing_files_are_named_in_the_error() {
+    let e = gaufre::compile(Path::new("exemple/none.gfr")).unwrap_err();
+    assert!(
+        matches!(&e, CompileError::Io { path, .. } if path == "exemple/none.gfr"),
+        "{}",
+        e
+    );
+    // an import that does not exist: reported in the importing file
+    let p = Project::new(
+        "lib-missing",
+        &[("main.gfr", "import \"b.gfr\"\nfn main() {}\n")],
+    );
+    let e = gaufre::compile(&p.path("main.gfr")).unwrap_err();
+    assert!(
+        matches!(&e, CompileError::Io { path, message }
+            if path.ends_with("main.gfr") && message.starts_with("import \"b.gfr\" not found")),
+        "{}",
+        e
+    );
+}
+
+#[test]
+fn defines_select_the_conditional_imports() {
+    let p = Project::new(
+        "lib-defines",
+        &[
+            (
+                "main.gfr",
+                "import \"debug.gfr\" if DEBUG\nfn main() {\n  call dbg();\n}\n",
+            ),
+            ("debug.gfr", "fn dbg() {\n  log(\"debug\");\n}\n"),
+        ],
+    );
+    let root = p.path("main.gfr");
+    let e = gaufre::compile(&root).unwrap_err();
+    assert!(matches!(e, CompileError::UnknownFunction { .. }), "{}", e);
+    let mut options = Options::default();
+    options.defines.insert("DEBUG".into(), String::new());
+    assert!(
+        gaufre::compile_with(&root, &options)
+            .unwrap()
+            .contains("(func $u_dbg")
+    );
+}
+
+#[test]
+fn long_operator_chain_is_an_error_not_a_crash() {
+    // every pass walks the expression tree recursively
+    let sum = |n| format!("x{}", " + x".repeat(n));
+    let program = |n| format!("fn main() {{\n  let x = 1;\n  log({});\n}}\n", sum(n));
+    let p = Project::new(
+        "lib-chain",
+        &[("ok.gfr", &program(100)), ("long.gfr", &program(3000))],
+    );
+    assert!(gaufre::compile(&p.path("ok.gfr")).is_ok());
+    let e = gaufre::compile(&p.path("long.gfr")).unwrap_err();
+    assert!(
+        matches!(&e, CompileError::Parse(ParseError::NestingTooDeep { .. })),
+        "{}",
+        e
+    );
+}
+
+#[test]
+fn long_else_if_chain_is_bundled_as_written() {
+    let arms: String = (0..1000).map(|i| format!(" else if n == {} {{\n    log({});\n  }}", i, i)).collect();
+    let src = format!("fn main() {{\n  let n = 999;\n  if n < 0 {{}}{}\n}}\n", arms);
+    let p = Project::new("lib-else-if", &[("main.gfr", &src)]);
+    let bundled = gaufre::bundle(&p.path("main.gfr"), &Options::default()).unwrap();
+    assert_eq!(bundled.matches("} else if n == ").count(), 1000, "{}", bundled);
+    assert!(gaufre::compile(&p.path("main.gfr")).is_ok());
+}
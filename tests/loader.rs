@@ -0,0 +1,179 @@
+mod common;
+
+use common::Project;
+use gaufre::{Options, error::CompileError};
+
+#[test]
+fn included_files_can_import_other_files() {
+    let p = Project::new(
+        "load-nested",
+        &[
+            (
+                "main.gfr",
+                "import \"lib/a.gfr\"\nfn main() {\n  call a();\n}\n",
+            ),
+            ("lib/a.gfr", "import \"b.gfr\"\nfn a() {\n  call b();\n}\n"),
+            ("lib/b.gfr", "fn b() {\n  log(\"b\");\n}\n"),
+        ],
+    );
+    let wat = gaufre::compile(&p.path("main.gfr")).unwrap();
+    assert!(
+        wat.contains("(func $u_a") && wat.contains("(func $u_b"),
+        "{}",
+        wat
+    );
+}
+
+#[test]
+fn a_file_imported_twice_is_loaded_once() {
+    // main -> a -> c and main -> b -> c
+    let p = Project::new(
+        "load-diamond",
+        &[
+            (
+                "main.gfr",
+                "import \"a.gfr\"\nimport \"b.gfr\"\nfn main() {}\n",
+            ),
+            ("a.gfr", "import \"c.gfr\"\nfn a() {}\n"),
+            ("b.gfr", "import \"./c.gfr\"\nfn b() {}\n"),
+            ("c.gfr", "fn c() {}\n"),
+        ],
+    );
+    let wat = gaufre::compile(&p.path("main.gfr")).unwrap();
+    assert_eq!(wat.matches("(func $u_c").count(), 1, "{}", wat);
+}
+
+#[test]
+fn import_cycle_lists_the_files() {
+    let p = Project::new(
+        "load-cycle",
+        &[
+            ("main.gfr", "import \"a.gfr\"\nfn main() {}\n"),
+            ("a.gfr", "import \"b.gfr\"\nfn a() {}\n"),
+            ("b.gfr", "import \"a.gfr\"\nfn b() {}\n"),
+        ],
+    );
+    let e = gaufre::compile(&p.path("main.gfr")).unwrap_err();
+    let CompileError::ImportCycle { chain } = &e else {
+        panic!("unexpected error {:?}", e);
+    };
+    let names: Vec<&str> = chain.iter().map(|f| &f[f.len() - 5..]).collect();
+    assert_eq!(names, ["a.gfr", "b.gfr", "a.gfr"]);
+    assert!(e.to_string().contains("circular import: "), "{}", e);
+}
+
+#[test]
+fn the_root_file_imported_again_is_a_cycle() {
+    let p = Project::new(
+        "load-self",
+        &[("main.gfr", "import \"main.gfr\"\nfn main() {}\n")],
+    );
+    let e = gaufre::compile(&p.path("main.gfr")).unwrap_err();
+    assert!(
+        matches!(&e, CompileError::ImportCycle { chain } if chain.len() == 2),
+        "{}",
+        e
+    );
+}
+
+#[test]
+fn imports_are_loaded_level_by_level() {
+    // the imports of main come before the imports of a
+    let p = Project::new(
+        "load-order",
+        &[
+            (
+                "main.gfr",
+                "import \"a.gfr\"\nimport \"b.gfr\"\nfn main() {}\n",
+            ),
+            ("a.gfr", "import \"c.gfr\"\nfn a() {}\n"),
+            ("b.gfr", "fn b() {}\n"),
+            ("c.gfr", "fn c() {}\n"),
+        ],
+    );
+    let wat = gaufre::compile(&p.path("main.gfr")).unwrap();
+    let at = |f: &str| wat.find(f).unwrap();
+    assert!(
+        at("(func $u_a") < at("(func $u_b") && at("(func $u_b") < at("(func $u_c"),
+        "{}",
+        wat
+    );
+}
+
+#[test]
+fn cycle_through_a_file_loaded_once() {
+    // c is loaded from a, and b closes the cycle b -> c -> b
+    let p = Project::new(
+        "load-cycle2",
+        &[
+            (
+                "main.gfr",
+                "import \"a.gfr\"\nimport \"b.gfr\"\nfn main() {}\n",
+            ),
+            ("a.gfr", "import \"c.gfr\"\nfn a() {}\n"),
+            ("b.gfr", "import \"c.gfr\"\nfn b() {}\n"),
+            ("c.gfr", "import \"b.gfr\"\nfn c() {}\n"),
+        ],
+    );
+    let e = gaufre::compile(&p.path("main.gfr")).unwrap_err();
+    let CompileError::ImportCycle { chain } = &e else {
+        panic!("unexpected error {:?}", e);
+    };
+    let names: Vec<&str> = chain.iter().map(|f| &f[f.len() - 5..]).collect();
+    assert_eq!(names, ["c.gfr", "b.gfr", "c.gfr"]);
+}
+
+#[test]
+fn search_directories_are_tried_in_order() {
+    let p = Project::new(
+        "load-search",
+        &[
+            (
+                "app/main.gfr",
+                "import \"util.gfr\"\nfn main() {\n  call util();\n}\n",
+            ),
+            ("first/util.gfr", "fn util() {\n  log(\"first\");\n}\n"),
+            ("second/util.gfr", "fn util() {\n  log(\"second\");\n}\n"),
+        ],
+    );
+    let options = |dirs: &[&str]| Options {
+        search_paths: dirs.iter().map(|d| p.path(d)).collect(),
+        ..Options::default()
+    };
+    let root = p.path("app/main.gfr");
+    let wat = gaufre::compile_with(&root, &options(&["second", "first"])).unwrap();
+    assert!(wat.contains("\"second\""), "{}", wat);
+    let wat = gaufre::compile_with(&root, &options(&["first", "second"])).unwrap();
+    assert!(wat.contains("\"first\""), "{}", wat);
+    assert!(gaufre::compile(&root).is_err());
+}
+
+#[test]
+fn duplicate_function_names_both_definitions() {
+    let p = Project::new(
+        "load-duplicate",
+        &[
+            (
+                "main.gfr",
+                "import \"a.gfr\"\nfn main() {}\nfn twice() {}\n",
+            ),
+            ("a.gfr", "\n  fn twice() {}\n"),
+        ],
+    );
+    let e = gaufre::compile(&p.path("main.gfr")).unwrap_err();
+    let CompileError::DuplicateFunction {
+        name,
+        first,
+        second,
+    } = &e
+    else {
+        panic!("unexpected error {}", e);
+    };
+    assert_eq!(name, "twice");
+    assert!(first.file.ends_with("main.gfr"), "{}", e);
+    assert_eq!((first.line, first.col), (3, 4));
+    assert!(second.file.ends_with("a.gfr"), "{}", e);
+    assert_eq!((second.line, second.col), (2, 6));
+    let message = format!("function `twice` already defined at {}:3:4", first.file);
+    assert!(e.to_string().ends_with(&message), "{}", e);
+}